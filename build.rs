@@ -0,0 +1,109 @@
+// Compiles shaders/*.comp, *.vert and *.frag to SPIR-V in OUT_DIR, where
+// `ShaderRegistry` embeds them from. `foo.comp` becomes `foo.spv`, `foo.vert` becomes
+// `foo.vert.spv`.
+//
+// The compiler is $GLSLC when set, otherwise glslc or glslangValidator from the
+// Vulkan SDK, whichever is on the PATH. Without one the binaries checked in next to
+// the sources are embedded instead, so after editing a shader refresh them with
+//
+//     WURMPLE_UPDATE_SHADERS=1 cargo build
+//
+// which writes what was compiled back into shaders/.
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const SHADER_DIR: &str = "shaders";
+const STAGES: &[&str] = &["comp", "vert", "frag"];
+
+fn spv_name(source: &Path) -> String {
+    let stem = source.file_stem().unwrap().to_str().unwrap();
+    match source.extension().and_then(|extension| extension.to_str()) {
+        Some("comp") => format!("{stem}.spv"),
+        Some(stage) => format!("{stem}.{stage}.spv"),
+        None => unreachable!(),
+    }
+}
+
+fn find_compiler() -> Option<PathBuf> {
+    if let Some(compiler) = env::var_os("GLSLC") {
+        return Some(PathBuf::from(compiler));
+    }
+    let path = env::var_os("PATH")?;
+    ["glslc", "glslangValidator"]
+        .into_iter()
+        .flat_map(|name| env::split_paths(&path).map(move |dir| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+fn compile(compiler: &Path, source: &Path, output: &Path) {
+    let mut command = Command::new(compiler);
+    if compiler
+        .file_stem()
+        .is_some_and(|stem| stem == "glslangValidator")
+    {
+        command.args(["-V", "--target-env", "vulkan1.0"]);
+    } else {
+        command.arg("--target-env=vulkan1.0");
+    }
+    let result = command.arg("-o").arg(output).arg(source).output();
+    match result {
+        Ok(result) if result.status.success() => {}
+        Ok(result) => panic!(
+            "{} failed to compile {source:?}:\n{}{}",
+            compiler.display(),
+            String::from_utf8_lossy(&result.stdout),
+            String::from_utf8_lossy(&result.stderr)
+        ),
+        Err(err) => panic!("Failed to run {}: {err}", compiler.display()),
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={SHADER_DIR}");
+    println!("cargo:rerun-if-env-changed=GLSLC");
+    println!("cargo:rerun-if-env-changed=PATH");
+    println!("cargo:rerun-if-env-changed=WURMPLE_UPDATE_SHADERS");
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let update = env::var_os("WURMPLE_UPDATE_SHADERS").is_some();
+    let compiler = find_compiler();
+    if compiler.is_none() {
+        assert!(
+            !update,
+            "WURMPLE_UPDATE_SHADERS is set but neither glslc nor glslangValidator was found"
+        );
+        println!(
+            "cargo:warning=No GLSL compiler found, embedding the prebuilt shaders in {SHADER_DIR}/"
+        );
+    }
+
+    let mut sources: Vec<PathBuf> = fs::read_dir(SHADER_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| STAGES.contains(&extension))
+        })
+        .collect();
+    sources.sort();
+    for source in sources {
+        let name = spv_name(&source);
+        let prebuilt = Path::new(SHADER_DIR).join(&name);
+        let output = out_dir.join(&name);
+        match &compiler {
+            Some(compiler) => {
+                compile(compiler, &source, &output);
+                if update {
+                    fs::copy(&output, &prebuilt).unwrap();
+                }
+            }
+            None => {
+                fs::copy(&prebuilt, &output)
+                    .unwrap_or_else(|err| panic!("No prebuilt {prebuilt:?}: {err}"));
+            }
+        }
+    }
+}
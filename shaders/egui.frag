@@ -0,0 +1,16 @@
+//GLSL version to use
+#version 450
+
+layout(location = 0) in vec2 inUV;
+layout(location = 1) in vec4 inColor;
+
+//texture and sampler are bound separately so each egui texture picks its own filtering
+layout(set = 0, binding = 0) uniform texture2D eguiTexture;
+layout(set = 0, binding = 1) uniform sampler eguiSampler;
+
+layout(location = 0) out vec4 outColor;
+
+void main()
+{
+    outColor = inColor * texture(sampler2D(eguiTexture, eguiSampler), inUV);
+}
@@ -0,0 +1,30 @@
+//GLSL version to use
+#version 450
+
+//egui::epaint::Vertex, positions are in points
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inUV;
+layout(location = 2) in vec4 inColor;
+
+layout(push_constant) uniform constants
+{
+    vec2 screenSize;
+} PushConstants;
+
+layout(location = 0) out vec2 outUV;
+layout(location = 1) out vec4 outColor;
+
+//egui hands out premultiplied sRGB colors but the draw image is linear
+vec3 linearFromGamma(vec3 srgb)
+{
+    vec3 lower = srgb / 12.92;
+    vec3 higher = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(higher, lower, step(srgb, vec3(0.04045)));
+}
+
+void main()
+{
+    gl_Position = vec4(2.0 * inPosition / PushConstants.screenSize - 1.0, 0.0, 1.0);
+    outUV = inUV;
+    outColor = vec4(linearFromGamma(inColor.rgb), inColor.a);
+}
@@ -0,0 +1,20 @@
+//GLSL version to use
+#version 450
+
+layout(location = 0) in vec3 inWorldPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inUV;
+layout(location = 3) in vec4 inColor;
+
+layout(location = 0) out vec4 outFragColor;
+
+//normalize(vec3(0.4, 1.0, 0.3))
+const vec3 lightDirection = vec3(0.3578, 0.8944, 0.2683);
+const float ambient = 0.15;
+
+void main()
+{
+    vec3 normal = normalize(inNormal);
+    float diffuse = max(dot(normal, lightDirection), 0.0);
+    outFragColor = vec4(inColor.rgb * (ambient + diffuse), inColor.a);
+}
@@ -0,0 +1,40 @@
+//GLSL version to use
+#version 450
+
+//mesh::Vertex
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inUV;
+layout(location = 3) in vec4 inTangent;
+
+layout(set = 0, binding = 0) uniform Camera
+{
+    mat4 view;
+    mat4 projection;
+    mat4 viewProjection;
+    mat4 inverseViewProjection;
+    vec4 position;
+    vec4 viewport;
+} camera;
+
+layout(push_constant) uniform constants
+{
+    mat4 model;
+    vec4 color;
+} PushConstants;
+
+layout(location = 0) out vec3 outWorldPosition;
+layout(location = 1) out vec3 outNormal;
+layout(location = 2) out vec2 outUV;
+layout(location = 3) out vec4 outColor;
+
+void main()
+{
+    vec4 worldPosition = PushConstants.model * vec4(inPosition, 1.0);
+    gl_Position = camera.viewProjection * worldPosition;
+    outWorldPosition = worldPosition.xyz;
+    //fine as long as the model matrix has no non-uniform scale
+    outNormal = mat3(PushConstants.model) * inNormal;
+    outUV = inUV;
+    outColor = PushConstants.color;
+}
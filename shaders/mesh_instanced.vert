@@ -0,0 +1,47 @@
+//GLSL version to use
+#version 450
+
+//mesh::Vertex
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inUV;
+layout(location = 3) in vec4 inTangent;
+
+layout(set = 0, binding = 0) uniform Camera
+{
+    mat4 view;
+    mat4 projection;
+    mat4 viewProjection;
+    mat4 inverseViewProjection;
+    vec4 position;
+    vec4 viewport;
+} camera;
+
+//indirect::InstanceData
+struct Instance
+{
+    mat4 model;
+    vec4 color;
+};
+
+layout(std430, set = 1, binding = 0) readonly buffer Instances
+{
+    Instance instances[];
+};
+
+layout(location = 0) out vec3 outWorldPosition;
+layout(location = 1) out vec3 outNormal;
+layout(location = 2) out vec2 outUV;
+layout(location = 3) out vec4 outColor;
+
+void main()
+{
+    //gl_InstanceIndex already includes the command's firstInstance
+    Instance instance = instances[gl_InstanceIndex];
+    vec4 worldPosition = instance.model * vec4(inPosition, 1.0);
+    gl_Position = camera.viewProjection * worldPosition;
+    outWorldPosition = worldPosition.xyz;
+    outNormal = mat3(instance.model) * inNormal;
+    outUV = inUV;
+    outColor = instance.color;
+}
@@ -1,31 +1,99 @@
-use muda::{dpi::PhysicalSize, Menu, PredefinedMenuItem};
+use std::time::Instant;
+
+use log::error;
+use muda::{Menu, PredefinedMenuItem};
 use winit::{
     application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::{StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow},
     window::{Window, WindowAttributes},
 };
 
-use crate::{engine::Engine};
+use crate::{
+    application::{AppConfig, Application, RedrawMode},
+    engine::{
+        input::InputBindings,
+        FrameClock,
+        ui::{UIRegistry, UI},
+        validation::ValidationConfig,
+        Engine,
+    },
+};
 
 pub struct App {
     window: Option<Window>,
     engine: Option<Engine>,
+    ui_registry: UIRegistry,
+    config: AppConfig,
+    application: Box<dyn Application>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(config: AppConfig, application: Box<dyn Application>) -> Self {
         Self {
             window: None,
             engine: None,
+            ui_registry: UIRegistry::new(),
+            config,
+            application,
+        }
+    }
+
+    // Panels registered before the engine exists are handed over once it is created.
+    pub fn register_ui<T: UI + 'static>(&mut self, name: &str, panel: T) {
+        match self.engine.as_mut() {
+            Some(engine) => engine.ui_registry().register(name, panel),
+            None => self.ui_registry.register(name, panel),
         }
     }
 }
 
 impl ApplicationHandler for App {
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        if let StartCause::ResumeTimeReached { .. } = cause {
+            if let Some(window) = &self.window {
+                window.request_redraw();
+            }
+        }
+    }
+
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        let window_attributes =
-            WindowAttributes::default().with_inner_size(PhysicalSize::new(1280.0, 720.0));
+        // Coming back from a suspend, only the surface needs rebuilding.
+        if let (Some(window), Some(engine)) = (self.window.as_ref(), self.engine.as_mut()) {
+            engine.resume(window);
+            window.request_redraw();
+            return;
+        }
+        let window_attributes = WindowAttributes::default()
+            .with_title(self.config.title.clone())
+            .with_inner_size(PhysicalSize::new(self.config.width, self.config.height));
         self.window = event_loop.create_window(window_attributes).ok();
-        self.engine = Engine::new(self.window.as_mut().unwrap()).ok();
+        self.engine = match Engine::with_shaders(
+            self.window.as_mut().unwrap(),
+            ValidationConfig::from_env(),
+            std::mem::take(&mut self.config.shader_registry),
+        ) {
+            Ok(engine) => Some(engine),
+            Err(err) => {
+                error!("Failed to initialize the engine: {err}");
+                event_loop.exit();
+                return;
+            }
+        };
+        if let Some(engine) = self.engine.as_mut() {
+            engine.set_clock(match self.config.frame_time {
+                Some(frame_time) => FrameClock::manual(frame_time, self.config.fixed_timestep),
+                None => FrameClock::realtime(self.config.fixed_timestep),
+            });
+            engine.input_mut().set_bindings(
+                InputBindings::from_env().unwrap_or_else(|| self.config.input_bindings.clone()),
+            );
+            engine
+                .ui_registry()
+                .append(std::mem::take(&mut self.ui_registry));
+            self.application.init(engine);
+        }
         let menu = Menu::new();
         menu.append_items(&[
             &PredefinedMenuItem::about(None, None),
@@ -43,6 +111,41 @@ impl ApplicationHandler for App {
         window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        self.engine.as_mut().unwrap().draw();
+        let (Some(window), Some(engine)) = (self.window.as_ref(), self.engine.as_mut()) else {
+            return;
+        };
+        let response = engine.on_window_event(window, &event);
+        match event {
+            WindowEvent::RedrawRequested => {
+                engine.update(self.application.as_mut());
+                let repaint_delay = engine.draw(window, self.application.as_mut());
+                if self.config.redraw_mode == RedrawMode::Continuous || repaint_delay.is_zero() {
+                    event_loop.set_control_flow(ControlFlow::Wait);
+                    window.request_redraw();
+                } else if let Some(repaint_at) = Instant::now().checked_add(repaint_delay) {
+                    event_loop.set_control_flow(ControlFlow::WaitUntil(repaint_at));
+                } else {
+                    event_loop.set_control_flow(ControlFlow::Wait);
+                }
+            }
+            WindowEvent::CloseRequested => event_loop.exit(),
+            _ if response.repaint => window.request_redraw(),
+            _ => {}
+        }
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(engine) = self.engine.as_mut() {
+            engine.suspend();
+        }
+    }
+
+    // The engine waits for the device to go idle when dropped, and has to go before the
+    // window its surface was created from.
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(mut engine) = self.engine.take() {
+            self.application.shutdown(&mut engine);
+        }
+        self.window = None;
     }
 }
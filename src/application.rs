@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use ash::{
+    vk::{
+        CommandBuffer, DescriptorPool, DescriptorSet, DescriptorSetAllocateInfo,
+        DescriptorSetLayout, PipelineBindPoint, PipelineLayout,
+    },
+    Device,
+};
+use egui::Context;
+use winit::{error::EventLoopError, event_loop::EventLoop};
+
+use crate::{
+    app::App,
+    engine::{
+        camera::{self, CameraUniforms, Projection, CAMERA_SET},
+        input::InputBindings,
+        AllocatedImage, DebugUtils, Engine, FrustumCuller, IndirectDraws, MeshDraw, MeshRenderer,
+        RingBuffer, ShaderRegistry,
+        DEFAULT_FIXED_STEP,
+    },
+};
+
+// `Continuous` draws a new frame as soon as the last one is done, `OnDemand` only when
+// input arrives or egui asks for a repaint.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RedrawMode {
+    #[default]
+    Continuous,
+    OnDemand,
+}
+
+pub struct AppConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub redraw_mode: RedrawMode,
+    pub fixed_timestep: Duration,
+    // When set every frame advances the clock by exactly this much instead of following
+    // the wall clock, so runs are reproducible.
+    pub frame_time: Option<Duration>,
+    // Replaced by the file WURMPLE_INPUT_BINDINGS points at, when it is set.
+    pub input_bindings: InputBindings,
+    // Handed to the engine when it starts, register shaders here before `run`.
+    pub shader_registry: ShaderRegistry,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            title: "WURMPLE".to_string(),
+            width: 1280,
+            height: 720,
+            redraw_mode: RedrawMode::default(),
+            fixed_timestep: DEFAULT_FIXED_STEP,
+            frame_time: None,
+            input_bindings: camera::default_bindings(),
+            shader_registry: ShaderRegistry::new(),
+        }
+    }
+}
+
+// Everything a custom pass needs to record into the frame. The draw image is in
+// GENERAL layout and has to be left that way, the background has already been drawn
+// and egui is drawn on top afterwards.
+pub struct RenderContext<'a> {
+    pub device: &'a Device,
+    pub command_buffer: CommandBuffer,
+    pub frame_index: usize,
+    pub queue_family_index: u32,
+    pub draw_image: &'a AllocatedImage,
+    pub debug_utils: &'a DebugUtils,
+    // What was uploaded for this frame, for CPU side culling and the like
+    pub camera: &'a CameraUniforms,
+    pub camera_set: DescriptorSet,
+    pub projection: Projection,
+    // Scratch memory for this frame's uniforms and dynamic geometry
+    pub ring: &'a RingBuffer,
+    // Reset every frame, sets from it live as long as the ring's allocations
+    pub descriptor_pool: DescriptorPool,
+    pub meshes: &'a MeshRenderer,
+    pub culler: &'a FrustumCuller,
+}
+
+impl RenderContext<'_> {
+    // Binds this frame's camera uniforms at CAMERA_SET, `layout` has to be created
+    // with `Engine::camera_layout` at that index.
+    pub fn bind_camera(&self, bind_point: PipelineBindPoint, layout: PipelineLayout) {
+        unsafe {
+            self.device.cmd_bind_descriptor_sets(
+                self.command_buffer,
+                bind_point,
+                layout,
+                CAMERA_SET,
+                &[self.camera_set],
+                &[],
+            )
+        }
+    }
+
+    pub fn allocate_descriptor_set(&self, layout: DescriptorSetLayout) -> DescriptorSet {
+        let layouts = [layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.descriptor_pool)
+            .set_layouts(&layouts);
+        unsafe { self.device.allocate_descriptor_sets(&allocate_info).unwrap()[0] }
+    }
+
+    // Draws `draws` over the draw image with the engine's basic mesh pipeline.
+    pub fn draw_meshes(&self, draws: &[MeshDraw]) {
+        self.meshes.draw(self, draws);
+    }
+
+    // Same result as `draw_meshes`, batched by mesh into indirect draws. Worth it once
+    // meshes repeat.
+    pub fn draw_meshes_instanced(&self, draws: &[MeshDraw]) {
+        self.draw_indirect(&IndirectDraws::build(self.ring, draws));
+    }
+
+    // Frustum culls `draws` on the GPU before drawing them, for scenes that don't fit
+    // in view at once.
+    pub fn draw_meshes_culled(&self, draws: &[MeshDraw]) {
+        let draws = IndirectDraws::build(self.ring, draws);
+        self.draw_indirect(&self.cull(&draws));
+    }
+
+    // Records the culling dispatch, see `FrustumCuller::cull`.
+    pub fn cull<'a>(&self, draws: &IndirectDraws<'a>) -> IndirectDraws<'a> {
+        self.culler.cull(self, draws)
+    }
+
+    // Draws batches built earlier in the frame, possibly rewritten by compute since.
+    pub fn draw_indirect(&self, draws: &IndirectDraws) {
+        self.meshes.draw_indirect(self, draws);
+    }
+}
+
+// Hooks for building on the engine without touching `App`. Every method has an
+// empty default, so implement only what you need.
+pub trait Application {
+    // Called once the window and the engine exist.
+    fn init(&mut self, _engine: &mut Engine) {}
+
+    // Called at the fixed rate from `AppConfig::fixed_timestep`, zero or more times per
+    // frame and always before `update`. Put simulation that has to be frame rate
+    // independent here.
+    fn fixed_update(&mut self, _engine: &mut Engine, _step: Duration) {}
+
+    // Called every frame before anything is recorded, `delta` is the time since the last one.
+    fn update(&mut self, _engine: &mut Engine, _delta: Duration) {}
+
+    // Called during the egui pass, after the engine's own panels.
+    fn ui(&mut self, _ctx: &Context) {}
+
+    // Records custom passes between the background and egui.
+    fn render(&mut self, _context: &RenderContext) {}
+
+    // Called before the engine is torn down.
+    fn shutdown(&mut self, _engine: &mut Engine) {}
+}
+
+// Nothing but the engine's own background and panels.
+impl Application for () {}
+
+// Owns the winit event loop until the window is closed.
+pub fn run<A: Application + 'static>(config: AppConfig, application: A) -> Result<(), EventLoopError> {
+    let event_loop = EventLoop::new()?;
+    let mut app = App::new(config, Box::new(application));
+    event_loop.run_app(&mut app)
+}
@@ -1,92 +1,327 @@
-use std::{io::Error, sync::Arc};
+use std::{
+    io::Error,
+    mem::size_of,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
 
-use allocated_image::AllocatedImage;
+use allocated_buffer::{create_host_visible_buffer, create_readback_buffer};
 use ash::vk::{
-    CommandBuffer, CommandBufferResetFlags, CommandBufferUsageFlags, Extent2D, Fence, ImageLayout,
-    PipelineBindPoint, PipelineStageFlags, PresentInfoKHR, Queue, SubmitInfo, SurfaceKHR,
+    AccessFlags, BufferImageCopy, BufferUsageFlags, CommandBuffer, CommandBufferResetFlags,
+    CommandBufferUsageFlags, DependencyFlags, DescriptorPoolCreateFlags, DescriptorSetLayout,
+    DescriptorSetLayoutCreateFlags, DescriptorType, Extent2D, Fence, ImageAspectFlags,
+    ImageLayout, ImageSubresourceLayers, MemoryBarrier, PipelineBindPoint, PipelineStageFlags,
+    PresentInfoKHR, Queue, RenderPass, Result as VkResult, ShaderStageFlags, SubmitInfo,
+    SurfaceKHR,
 };
+use camera::{Camera, CameraUniforms, OrbitCamera};
+use components::{write_uniform_buffer, DescriptorAllocato, DescriptorLayoutBuilder, PoolSizeRatio};
 use command_buffers::{allocate_command_buffer, begin_command_buffer, create_command_pool};
 use configuration::{VkConfiguration, MAX_FRAMES};
 use data::FrameData;
 use egui_renderer::{ConfigurationParameter, EGUIRenderer, Renderer};
 use image_ops::image_transition;
+use immediate_submit::ImmediateSubmit;
+use mesh::{upload_mesh, Mesh, MeshData};
+use input::InputState;
+use state::EngineState;
+use validation::{ValidationConfig, ValidationSink};
 use sync_objects::{create_fence, create_semaphore};
-use winit::window::Window;
+use ui::{Inspector, Profiler, Settings, UIRegistry, UI};
+use egui::{TextureId, TextureOptions};
+use egui_winit::EventResponse;
+use glam::Vec2;
+use handles::Owned;
+use winit::{event::WindowEvent, window::Window};
+
+use crate::application::{Application, RenderContext};
 
+mod allocated_buffer;
 mod allocated_image;
+pub mod camera;
+mod clock;
 mod command_buffers;
 mod components;
 mod configuration;
+mod culling;
 mod data;
+mod debug_utils;
 mod deletion_queue;
+mod device_info;
+mod egui_callback;
 mod egui_renderer;
+mod handles;
 mod image_ops;
+mod immediate_submit;
+mod indirect;
+pub mod input;
+mod memory_stats;
+pub mod mesh;
+mod mesh_renderer;
+mod ring_buffer;
+pub mod scene;
+pub mod object_tracker;
+pub mod state;
 mod sync_objects;
-mod ui;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod ui;
+pub mod validation;
+
+pub use allocated_image::AllocatedImage;
+pub use ring_buffer::{RingAllocation, RingBuffer};
+pub use clock::{FrameClock, FrameTime, DEFAULT_FIXED_STEP};
+pub use components::{ComputePushConstants, QueueFamilyIndices, ShaderRegistry, SHADER_DIR_ENV};
+pub use culling::{CullingStats, FrustumCuller};
+pub use debug_utils::{DebugLabel, DebugUtils};
+pub use device_info::DeviceInfo;
+pub use indirect::{IndirectBatch, IndirectDraws, InstanceData};
+pub use mesh_renderer::{MeshDraw, MeshRenderer};
+pub use memory_stats::{
+    category_stats, default_report_path, AllocationCategory, CategoryStats, HeapInfo, MemoryReport,
+    MemoryStats,
+};
+pub use egui_callback::{CallbackContext, CallbackFn};
 
+// Starting size of each frame's ring buffer, it grows when a frame needs more.
+const FRAME_RING_SIZE: u64 = 1024 * 1024;
+// Descriptor sets each frame can hand out through `RenderContext::allocate_descriptor_set`.
+const FRAME_DESCRIPTOR_SETS: u32 = 256;
+
+// Dropping the engine waits for the device and then tears everything down, fields
+// go in declaration order so `configuration` has to stay last.
 pub struct Engine {
-    configuration: VkConfiguration,
     frame_data: Vec<FrameData>,
+    // Owns the pool the per-frame descriptor sets come from
+    #[allow(dead_code)]
+    frame_descriptor_allocator: DescriptorAllocato,
+    camera_layout: Owned<DescriptorSetLayout>,
     current_frame: usize,
     egui_renderer: EGUIRenderer,
+    state: EngineState,
+    ui_registry: UIRegistry,
+    clock: FrameClock,
+    input: InputState,
+    camera: Box<dyn Camera>,
+    immediate: ImmediateSubmit,
+    mesh_renderer: MeshRenderer,
+    culler: FrustumCuller,
+    configuration: VkConfiguration,
 }
 #[allow(dead_code)]
 impl Engine {
     pub fn new(window: &Window) -> Result<Self, Error> {
-        let configuration = VkConfiguration::new(window);
+        Self::with_validation(window, ValidationConfig::from_env())
+    }
+
+    pub fn with_validation(window: &Window, validation: ValidationConfig) -> Result<Self, Error> {
+        Self::with_shaders(window, validation, ShaderRegistry::new())
+    }
+
+    // The engine's pipelines look their shaders up in `shader_registry`, registering a
+    // shader under a built-in name replaces it.
+    pub fn with_shaders(
+        window: &Window,
+        validation: ValidationConfig,
+        shader_registry: ShaderRegistry,
+    ) -> Result<Self, Error> {
+        let configuration = VkConfiguration::new(window, validation, shader_registry);
         let mut frame_data = Vec::new();
-        let command_pool = create_command_pool(
+        let command_pool = Arc::new(create_command_pool(
             &configuration.device,
             configuration.indices.graphics_q_idx.unwrap(),
-        );
+        ));
         let current_frame = 0;
-        for _i in 0..MAX_FRAMES {
-            let command_buffer = allocate_command_buffer(&configuration.device, command_pool);
+        let debug_utils = &configuration.debug_utils;
+        debug_utils.name(**command_pool, "frame command pool");
+        let mut layout_builder = DescriptorLayoutBuilder::new();
+        layout_builder.add_binding(0, DescriptorType::UNIFORM_BUFFER);
+        let camera_layout = layout_builder.build(
+            configuration.device.clone(),
+            ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE,
+            DescriptorSetLayoutCreateFlags::empty(),
+        );
+        let frame_descriptor_allocator = DescriptorAllocato::new(
+            configuration.device.clone(),
+            MAX_FRAMES,
+            vec![PoolSizeRatio::new(DescriptorType::UNIFORM_BUFFER, 1.0)],
+            DescriptorPoolCreateFlags::empty(),
+        );
+        debug_utils.name(*camera_layout, "camera descriptor layout");
+        debug_utils.name(frame_descriptor_allocator.pool(), "frame descriptor pool");
+        let limits = unsafe {
+            configuration
+                .instance
+                .get_physical_device_properties(configuration.physical_device)
+        }
+        .limits;
+        let ring_alignment = limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment);
+        for i in 0..MAX_FRAMES {
+            let command_buffer = allocate_command_buffer(&configuration.device, **command_pool);
             let fence = create_fence(&configuration.device);
             let swapchain_semaphore = create_semaphore(&configuration.device);
             let render_semaphore = create_semaphore(&configuration.device);
+            debug_utils.name(command_buffer, &format!("frame {i} command buffer"));
+            debug_utils.name(*fence, &format!("frame {i} render fence"));
+            debug_utils.name(*swapchain_semaphore, &format!("frame {i} swapchain semaphore"));
+            debug_utils.name(*render_semaphore, &format!("frame {i} render semaphore"));
+            let camera_buffer = create_host_visible_buffer(
+                &configuration.vma_allocator,
+                size_of::<CameraUniforms>() as u64,
+                BufferUsageFlags::UNIFORM_BUFFER,
+            )?;
+            let camera_set = frame_descriptor_allocator
+                .allocate(&configuration.device, &[*camera_layout]);
+            write_uniform_buffer(
+                &configuration.device,
+                camera_set,
+                0,
+                camera_buffer.buffer,
+                camera_buffer.size,
+            );
+            debug_utils.name(camera_buffer.buffer, &format!("frame {i} camera uniforms"));
+            debug_utils.name(camera_set, &format!("frame {i} camera descriptor"));
+            let ring = RingBuffer::new(
+                &configuration.vma_allocator,
+                debug_utils,
+                &format!("frame {i} ring buffer"),
+                FRAME_RING_SIZE,
+                ring_alignment,
+            )?;
+            let descriptor_allocator = DescriptorAllocato::new(
+                configuration.device.clone(),
+                FRAME_DESCRIPTOR_SETS,
+                vec![
+                    PoolSizeRatio::new(DescriptorType::STORAGE_BUFFER, 4.0),
+                    PoolSizeRatio::new(DescriptorType::UNIFORM_BUFFER, 2.0),
+                ],
+                DescriptorPoolCreateFlags::empty(),
+            );
+            debug_utils.name(
+                descriptor_allocator.pool(),
+                &format!("frame {i} descriptor pool"),
+            );
             frame_data.push(FrameData::new(
-                command_pool,
+                command_pool.clone(),
                 command_buffer,
                 swapchain_semaphore,
                 render_semaphore,
                 fence,
+                camera_buffer,
+                camera_set,
+                ring,
+                descriptor_allocator,
             ));
         }
         let mut egui_renderer = EGUIRenderer::new(
             ConfigurationParameter {
                 device: configuration.device.clone(),
                 window,
-                gfx_queue: configuration.graphics_queue.clone(),
-                surface: configuration.surface.clone(),
+                allocator: configuration.vma_allocator.clone(),
+                shader_registry: &configuration.shader_registry,
+                pipeline_cache: *configuration.pipeline_cache.cache,
+                debug_utils: configuration.debug_utils.clone(),
             },
             configuration.indices.graphics_q_idx.unwrap(),
         );
 
-        egui_renderer.buffer_allocation(configuration.vma_allocator.clone(), configuration.extent);
+        let immediate = ImmediateSubmit::new(
+            &configuration.device,
+            configuration.graphics_queue.clone(),
+            configuration.indices.graphics_q_idx.unwrap(),
+        );
+        immediate.name_objects(&configuration.debug_utils);
+        let features = unsafe {
+            configuration
+                .instance
+                .get_physical_device_features(configuration.physical_device)
+        };
+        let mesh_renderer = MeshRenderer::new(
+            &configuration.device,
+            &configuration.vma_allocator,
+            &configuration.allocated_image,
+            *camera_layout,
+            &configuration.shader_registry,
+            *configuration.pipeline_cache.cache,
+            features.multi_draw_indirect != 0,
+            &configuration.debug_utils,
+        )?;
+        let culler = FrustumCuller::new(
+            &configuration.device,
+            &configuration.vma_allocator,
+            *camera_layout,
+            &configuration.shader_registry,
+            *configuration.pipeline_cache.cache,
+            &configuration.debug_utils,
+        )?;
+
+        let mut state = EngineState::new(&configuration.background_effects);
+        state.device_info = DeviceInfo::new(&configuration);
+        let draw_image_texture = egui_renderer.register_user_texture(
+            configuration.allocated_image.clone(),
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            TextureOptions::LINEAR,
+        );
+        state
+            .textures
+            .insert("draw_image".to_string(), draw_image_texture);
+        let mut ui_registry = UIRegistry::new()
+            .with_panel("Settings", Settings::default())
+            .with_panel("Inspector", Inspector::default())
+            .with_panel("Profiler", Profiler::default());
+        ui_registry.set_open("Inspector", false);
+        ui_registry.set_open("Profiler", false);
         Ok(Self {
             configuration,
             frame_data,
+            frame_descriptor_allocator,
+            camera_layout,
             current_frame,
             egui_renderer,
+            state,
+            ui_registry,
+            clock: FrameClock::default(),
+            input: InputState::new(camera::default_bindings()),
+            camera: Box::new(OrbitCamera::default()),
+            immediate,
+            mesh_renderer,
+            culler,
         })
     }
 
     fn draw_background(&self, image: Arc<AllocatedImage>, command_buffer: CommandBuffer) {
+        let Some(effect) = self
+            .configuration
+            .background_effects
+            .get(self.state.current_effect)
+        else {
+            return;
+        };
+        let parameters = &self.state.effects[self.state.current_effect].parameters;
         unsafe {
             self.configuration.device.cmd_bind_pipeline(
                 command_buffer,
                 PipelineBindPoint::COMPUTE,
-                self.configuration.compute_pipeline,
+                *effect.pipeline,
             );
             self.configuration.device.cmd_bind_descriptor_sets(
                 command_buffer,
                 PipelineBindPoint::COMPUTE,
-                self.configuration.compute_pipeline_layout,
+                **effect.layout,
                 0,
                 &[self.configuration.descriptor_set],
                 &[],
             );
+            self.configuration.device.cmd_push_constants(
+                command_buffer,
+                **effect.layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                parameters.as_bytes(),
+            );
 
             self.configuration.device.cmd_dispatch(
                 command_buffer,
@@ -97,31 +332,89 @@ impl Engine {
         }
     }
 
-    pub fn draw(&mut self) {
+    // egui sees every event first, whatever it claims is kept away from the scene's input.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> EventResponse {
+        let response = self.egui_renderer.on_window_event(window, event);
+        let context = self.egui_renderer.context();
+        self.input.on_window_event(
+            event,
+            context.wants_keyboard_input(),
+            context.wants_pointer_input(),
+        );
+        response
+    }
+
+    // Returns how long egui wants to wait before the next frame. Nothing is drawn while
+    // suspended, there is no surface to present to.
+    pub fn draw(&mut self, window: &Window, application: &mut dyn Application) -> Duration {
+        let Some(presentation) = &self.configuration.presentation else {
+            return Duration::MAX;
+        };
+        let time = self.clock.time();
+        self.state.frame_time = time;
+        if let Some(parameters) = self.state.current_parameters() {
+            parameters.time = time.total_seconds();
+            parameters.delta_time = time.delta_seconds();
+            parameters.frame = time.frame as u32;
+        }
+        self.state
+            .memory
+            .update(&self.configuration.vma_allocator);
+        let (ui_registry, state) = (&mut self.ui_registry, &mut self.state);
+        let repaint_delay = self.egui_renderer.prepare_frame(window, &mut |ctx| {
+            ui_registry.ui(ctx, state);
+            application.ui(ctx);
+        });
+        let fences = vec![*self.frame_data[self.current_frame].render_fence];
+        unsafe {
+            self.configuration
+                .device
+                .wait_for_fences(&fences, true, u64::MAX)
+                .unwrap();
+        }
+        // Resizing the window or coming back from a suspend can leave the swapchain out
+        // of date, rebuild it and skip the frame. The fence is only reset once an image
+        // was acquired, otherwise the next frame would wait for a submit that never came.
+        let acquired = unsafe {
+            self.configuration.swapchain_device.acquire_next_image(
+                *presentation.swapchain,
+                u64::MAX,
+                *self.frame_data[self.current_frame].swapchain_semaphore,
+                Fence::null(),
+            )
+        };
+        let (image_index, suboptimal) = match acquired {
+            Ok(acquired) => acquired,
+            Err(VkResult::ERROR_OUT_OF_DATE_KHR) => {
+                self.recreate_presentation(window);
+                return Duration::ZERO;
+            }
+            Err(err) => panic!("Failed to acquire a swapchain image: {err}"),
+        };
+        unsafe { self.configuration.device.reset_fences(&fences).unwrap() };
+        self.frame_data[self.current_frame].deletion_queue.flush();
+        self.frame_data[self.current_frame].ring.reset();
+        self.frame_data[self.current_frame]
+            .descriptor_allocator
+            .reset_descriptors(&self.configuration.device);
+        self.culler.begin_frame(self.current_frame);
+        self.state.culling = self.culler.stats();
+        let camera = self.camera.uniforms(self.viewport());
+        let projection = self.camera.projection();
+        self.frame_data[self.current_frame]
+            .camera_buffer
+            .write(0, camera.as_bytes());
         let current_frame_data = &self.frame_data[self.current_frame];
         let command_buffer = current_frame_data.command_buffer;
         let device = &self.configuration.device;
-        let fences = vec![current_frame_data.render_fence];
+        let debug_utils = &self.configuration.debug_utils;
         let allocated_image = self.configuration.allocated_image.image;
         let alloc_extent = Extent2D {
             width: self.configuration.allocated_image.extent.width,
             height: self.configuration.allocated_image.extent.height,
         };
 
-        unsafe {
-            device.wait_for_fences(&fences, true, u64::MAX).unwrap();
-            device.reset_fences(&fences).unwrap();
-            let next_image = self
-                .configuration
-                .swapchain_device
-                .acquire_next_image(
-                    self.configuration.swapchain,
-                    u64::MAX,
-                    current_frame_data.swapchain_semaphore,
-                    Fence::null(),
-                )
-                .unwrap();
-
+        let out_of_date = unsafe {
             device
                 .reset_command_buffer(
                     current_frame_data.command_buffer,
@@ -144,7 +437,27 @@ impl Engine {
                 ImageLayout::GENERAL,
             );
 
+            let label = debug_utils.label(command_buffer, "background");
             self.draw_background(self.configuration.allocated_image.clone(), command_buffer);
+            drop(label);
+
+            let label = debug_utils.label(command_buffer, "application");
+            application.render(&RenderContext {
+                device,
+                command_buffer,
+                frame_index: self.current_frame,
+                queue_family_index: self.configuration.indices.graphics_q_idx.unwrap(),
+                draw_image: &self.configuration.allocated_image,
+                debug_utils,
+                camera: &camera,
+                camera_set: current_frame_data.camera_set,
+                projection,
+                ring: &current_frame_data.ring,
+                descriptor_pool: current_frame_data.descriptor_allocator.pool(),
+                meshes: &self.mesh_renderer,
+                culler: &self.culler,
+            });
+            drop(label);
 
             image_transition(
                 device,
@@ -152,6 +465,26 @@ impl Engine {
                 self.configuration.indices.graphics_q_idx.unwrap(),
                 allocated_image,
                 ImageLayout::GENERAL,
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            );
+
+            let label = debug_utils.label(command_buffer, "egui");
+            self.egui_renderer.draw(
+                command_buffer,
+                self.current_frame,
+                self.configuration.allocated_image.clone(),
+                &current_frame_data.ring,
+            );
+            drop(label);
+
+            let label = debug_utils.label(command_buffer, "present blit");
+
+            image_transition(
+                device,
+                current_frame_data.command_buffer,
+                self.configuration.indices.graphics_q_idx.unwrap(),
+                allocated_image,
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
                 ImageLayout::TRANSFER_SRC_OPTIMAL,
             );
 
@@ -159,7 +492,7 @@ impl Engine {
                 device,
                 current_frame_data.command_buffer,
                 self.configuration.indices.graphics_q_idx.unwrap(),
-                self.configuration.images[next_image.0 as usize],
+                presentation.images[image_index as usize],
                 ImageLayout::UNDEFINED,
                 ImageLayout::TRANSFER_DST_OPTIMAL,
             );
@@ -168,29 +501,28 @@ impl Engine {
                 device,
                 current_frame_data.command_buffer,
                 allocated_image,
-                self.configuration.images[next_image.0 as usize],
+                presentation.images[image_index as usize],
                 alloc_extent,
-                self.configuration.extent,
+                presentation.extent,
             );
 
-            self.egui_renderer.draw(self.configuration.allocated_image.clone());
-
             image_transition(
                 device,
                 current_frame_data.command_buffer,
                 self.configuration.indices.graphics_q_idx.unwrap(),
-                self.configuration.images[next_image.0 as usize],
+                presentation.images[image_index as usize],
                 ImageLayout::TRANSFER_DST_OPTIMAL,
                 ImageLayout::PRESENT_SRC_KHR,
             );
+            drop(label);
 
             device
                 .end_command_buffer(current_frame_data.command_buffer)
                 .unwrap();
 
             let command_buffers = vec![current_frame_data.command_buffer];
-            let wait_semaphores = vec![current_frame_data.swapchain_semaphore];
-            let signal_semaphores = vec![current_frame_data.render_semaphore];
+            let wait_semaphores = vec![*current_frame_data.swapchain_semaphore];
+            let signal_semaphores = vec![*current_frame_data.render_semaphore];
             let dst_stage_mask = vec![PipelineStageFlags::ALL_COMMANDS];
             let submit_info = SubmitInfo::default()
                 .command_buffers(&command_buffers)
@@ -202,44 +534,226 @@ impl Engine {
                 .queue_submit(
                     *self.configuration.graphics_queue,
                     &[submit_info],
-                    current_frame_data.render_fence,
+                    *current_frame_data.render_fence,
                 )
                 .unwrap();
 
-            let swapchains = vec![self.configuration.swapchain];
-            let indices = vec![next_image.0];
+            let swapchains = vec![*presentation.swapchain];
+            let indices = vec![image_index];
             let present_info = PresentInfoKHR::default()
                 .wait_semaphores(&signal_semaphores)
                 .swapchains(&swapchains)
                 .image_indices(&indices);
 
-            self.configuration
+            let presented = self
+                .configuration
                 .swapchain_device
-                .queue_present(*self.configuration.graphics_queue, &present_info)
-                .unwrap();
+                .queue_present(*self.configuration.graphics_queue, &present_info);
             self.current_frame = (self.current_frame + 1) % MAX_FRAMES as usize;
+            match presented {
+                Ok(present_suboptimal) => suboptimal || present_suboptimal,
+                Err(VkResult::ERROR_OUT_OF_DATE_KHR) => true,
+                Err(err) => panic!("Failed to present: {err}"),
+            }
+        };
+        if out_of_date {
+            self.recreate_presentation(window);
         }
+        self.configuration.validation_sink.check();
+        repaint_delay
+    }
+
+    // `layout` is the layout the image is left in when the egui pass records.
+    pub fn register_user_texture(
+        &mut self,
+        name: &str,
+        image: Arc<AllocatedImage>,
+        layout: ImageLayout,
+        options: TextureOptions,
+    ) -> TextureId {
+        let id = self
+            .egui_renderer
+            .register_user_texture(image, layout, options);
+        self.state.textures.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn free_user_texture(&mut self, name: &str) {
+        if let Some(id) = self.state.textures.remove(name) {
+            self.egui_renderer.free_user_texture(id);
+        }
+    }
+
+    // Pipelines recorded from egui paint callbacks have to be compatible with this pass.
+    pub fn egui_render_pass(&self) -> RenderPass {
+        self.egui_renderer.render_pass()
+    }
+
+    pub fn write_memory_report(&self, path: &Path) -> Result<(), Error> {
+        self.state.memory.write_report(path)
+    }
+
+    // Copies out the draw image as the last frame left it, rows of RGBA16F pixels with no
+    // padding. Waits for the device, so it is meant for tests and screenshots.
+    pub fn read_draw_image(&self) -> Result<Vec<u8>, Error> {
+        let image = &self.configuration.allocated_image;
+        let size = image.extent.width as u64 * image.extent.height as u64 * 8;
+        let buffer = create_readback_buffer(
+            &self.configuration.vma_allocator,
+            size,
+            BufferUsageFlags::TRANSFER_DST,
+        )?;
+        unsafe { self.configuration.device.device_wait_idle().unwrap() };
+        self.immediate.submit(|device, command_buffer| {
+            let region = BufferImageCopy::default()
+                .image_subresource(
+                    ImageSubresourceLayers::default()
+                        .aspect_mask(ImageAspectFlags::COLOR)
+                        .layer_count(1),
+                )
+                .image_extent(image.extent);
+            let barrier = MemoryBarrier::default()
+                .src_access_mask(AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(AccessFlags::HOST_READ);
+            unsafe {
+                device.cmd_copy_image_to_buffer(
+                    command_buffer,
+                    image.image,
+                    ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    buffer.buffer,
+                    &[region],
+                );
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    PipelineStageFlags::TRANSFER,
+                    PipelineStageFlags::HOST,
+                    DependencyFlags::empty(),
+                    &[barrier],
+                    &[],
+                    &[],
+                );
+            }
+        });
+        let mut pixels = vec![0; size as usize];
+        buffer.read(0, &mut pixels);
+        Ok(pixels)
+    }
+
+    pub fn validation(&self) -> Arc<ValidationSink> {
+        self.configuration.validation_sink.clone()
+    }
+
+    pub fn ui_registry(&mut self) -> &mut UIRegistry {
+        &mut self.ui_registry
+    }
+
+    // For custom passes building their own pipelines from registered shaders.
+    pub fn shader_registry(&self) -> &ShaderRegistry {
+        &self.configuration.shader_registry
     }
 
     pub fn get_graphics_queue(&self) -> Arc<Queue> {
         self.configuration.graphics_queue.clone()
     }
 
-    pub fn get_surface(&self) -> Arc<SurfaceKHR> {
-        self.configuration.surface.clone()
+    pub fn get_surface(&self) -> Option<SurfaceKHR> {
+        self.configuration
+            .presentation
+            .as_ref()
+            .map(|presentation| *presentation.surface)
     }
 
-    pub fn cleanup(&self) {
-        unsafe {
-            let device = &self.configuration.device;
-            for i in 0..MAX_FRAMES {
-                let frame = &self.frame_data[i as usize];
-                device.destroy_command_pool(frame.command_pool, None);
-                device.destroy_fence(frame.render_fence, None);
-                device.destroy_semaphore(frame.render_semaphore, None);
-                device.destroy_semaphore(frame.swapchain_semaphore, None);
-            }
-            self.configuration.cleanup();
+    // Advances the clock by one frame and runs the application's simulation: as many
+    // fixed steps as the elapsed time owes, then the per-frame update.
+    pub fn update(&mut self, application: &mut dyn Application) {
+        let time = self.clock.tick();
+        let viewport = self.viewport();
+        self.camera.update(&self.input, time.delta, viewport);
+        while self.clock.fixed_step() {
+            application.fixed_update(self, self.clock.fixed_step_duration());
         }
+        application.update(self, time.delta);
+        self.input.end_frame();
+    }
+
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut InputState {
+        &mut self.input
+    }
+
+    pub fn camera(&self) -> &dyn Camera {
+        self.camera.as_ref()
+    }
+
+    pub fn set_camera<C: Camera + 'static>(&mut self, camera: C) {
+        self.camera = Box::new(camera);
+    }
+
+    // Pipelines that read the camera put this at `camera::CAMERA_SET` in their layout.
+    pub fn camera_layout(&self) -> DescriptorSetLayout {
+        *self.camera_layout
+    }
+
+    // Uploads geometry for `RenderContext::draw_meshes`, blocking until the copy is done.
+    pub fn upload_mesh(&self, data: &MeshData) -> Result<Mesh, Error> {
+        upload_mesh(
+            &self.configuration.vma_allocator,
+            &self.immediate,
+            &self.configuration.debug_utils,
+            data,
+        )
+    }
+
+    // Reads an .obj, .gltf or .glb file and uploads everything in it as one mesh.
+    pub fn load_mesh(&self, path: &Path) -> Result<Mesh, Error> {
+        self.upload_mesh(&MeshData::load(path)?)
+    }
+
+    fn viewport(&self) -> Vec2 {
+        let extent = self.configuration.allocated_image.extent;
+        Vec2::new(extent.width as f32, extent.height as f32)
+    }
+
+    pub fn clock(&self) -> &FrameClock {
+        &self.clock
+    }
+
+    // Replaces the clock, e.g. with `FrameClock::manual` for reproducible frames.
+    pub fn set_clock(&mut self, clock: FrameClock) {
+        self.clock = clock;
+    }
+
+    pub fn suspend(&mut self) {
+        self.configuration.suspend();
+    }
+
+    pub fn resume(&mut self, window: &Window) {
+        self.configuration.resume(window);
+        self.clock.resync();
+        self.state.device_info = DeviceInfo::new(&self.configuration);
+    }
+
+    fn recreate_presentation(&mut self, window: &Window) {
+        self.configuration.recreate_presentation(window);
+        self.state.device_info = DeviceInfo::new(&self.configuration);
+    }
+
+    // Keeps `object` alive until the most recently submitted frame has finished on the
+    // GPU, for resources that in-flight command buffers may still reference. Frames
+    // finish in submission order, so earlier ones are done by then as well.
+    pub fn retire<T: Send + Sync + 'static>(&mut self, object: T) {
+        let last_submitted = (self.current_frame + MAX_FRAMES as usize - 1) % MAX_FRAMES as usize;
+        self.frame_data[last_submitted]
+            .deletion_queue
+            .enqueue(move || drop(object));
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        unsafe { self.configuration.device.device_wait_idle().unwrap() };
     }
 }
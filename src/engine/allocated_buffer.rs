@@ -0,0 +1,163 @@
+use std::{
+    io::{Error, ErrorKind},
+    sync::Arc,
+};
+
+use ash::vk::{Buffer, BufferCreateInfo, BufferUsageFlags, DeviceSize, SharingMode};
+use vk_mem::{Alloc, Allocation, AllocationCreateFlags, AllocationCreateInfo, Allocator, MemoryUsage};
+
+use super::{
+    memory_stats::{track_allocation, track_free, AllocationCategory},
+    object_tracker::{track, untrack},
+};
+
+// Owns the buffer and its memory, both released on drop.
+pub struct AllocatedBuffer {
+    pub buffer: Buffer,
+    pub allocation: Allocation,
+    pub size: DeviceSize,
+    pub category: AllocationCategory,
+    allocator: Arc<Allocator>,
+}
+
+impl AllocatedBuffer {
+    #[track_caller]
+    pub fn new(
+        allocator: Arc<Allocator>,
+        buffer: Buffer,
+        allocation: Allocation,
+        size: DeviceSize,
+        category: AllocationCategory,
+    ) -> Self {
+        track(buffer);
+        Self {
+            buffer,
+            allocation,
+            size,
+            category,
+            allocator,
+        }
+    }
+
+    pub fn write(&mut self, offset: DeviceSize, data: &[u8]) {
+        assert!(offset + data.len() as DeviceSize <= self.size);
+        let allocator = &self.allocator;
+        let mapped_data = allocator.get_allocation_info(&self.allocation).mapped_data as *mut u8;
+        assert!(!mapped_data.is_null(), "buffer is not host visible");
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                mapped_data.add(offset as usize),
+                data.len(),
+            );
+        }
+        allocator
+            .flush_allocation(&self.allocation, offset, data.len() as DeviceSize)
+            .unwrap();
+    }
+
+    // Only for buffers from `create_readback_buffer`, reads from write combined memory
+    // are very slow.
+    pub fn read(&self, offset: DeviceSize, data: &mut [u8]) {
+        assert!(offset + data.len() as DeviceSize <= self.size);
+        let allocator = &self.allocator;
+        let mapped_data = allocator.get_allocation_info(&self.allocation).mapped_data as *const u8;
+        assert!(!mapped_data.is_null(), "buffer is not host visible");
+        allocator
+            .invalidate_allocation(&self.allocation, offset, data.len() as DeviceSize)
+            .unwrap();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                mapped_data.add(offset as usize),
+                data.as_mut_ptr(),
+                data.len(),
+            );
+        }
+    }
+}
+
+impl Drop for AllocatedBuffer {
+    fn drop(&mut self) {
+        track_free(&self.allocator, &self.allocation, self.category);
+        untrack(self.buffer);
+        unsafe { self.allocator.destroy_buffer(self.buffer, &mut self.allocation) }
+    }
+}
+
+#[track_caller]
+pub fn create_allocated_buffer(
+    allocator: &Arc<Allocator>,
+    size: DeviceSize,
+    usage: BufferUsageFlags,
+    memory_usage: MemoryUsage,
+    flags: AllocationCreateFlags,
+    category: AllocationCategory,
+) -> Result<AllocatedBuffer, Error> {
+    let buffer_create_info = BufferCreateInfo::default()
+        .size(size)
+        .usage(usage)
+        .sharing_mode(SharingMode::EXCLUSIVE);
+    let allocation_create_info = AllocationCreateInfo {
+        usage: memory_usage,
+        flags,
+        ..Default::default()
+    };
+    let (buffer, allocation) = unsafe {
+        allocator
+            .create_buffer(&buffer_create_info, &allocation_create_info)
+            .map_err(|err| Error::new(ErrorKind::OutOfMemory, err))?
+    };
+    track_allocation(allocator, &allocation, category);
+    Ok(AllocatedBuffer::new(
+        allocator.clone(),
+        buffer,
+        allocation,
+        size,
+        category,
+    ))
+}
+
+#[track_caller]
+pub fn create_host_visible_buffer(
+    allocator: &Arc<Allocator>,
+    size: DeviceSize,
+    usage: BufferUsageFlags,
+) -> Result<AllocatedBuffer, Error> {
+    create_allocated_buffer(
+        allocator,
+        size,
+        usage,
+        MemoryUsage::Auto,
+        AllocationCreateFlags::MAPPED | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+        AllocationCategory::Buffer,
+    )
+}
+
+// Host visible and cached, for results the CPU reads back.
+#[track_caller]
+pub fn create_readback_buffer(
+    allocator: &Arc<Allocator>,
+    size: DeviceSize,
+    usage: BufferUsageFlags,
+) -> Result<AllocatedBuffer, Error> {
+    create_allocated_buffer(
+        allocator,
+        size,
+        usage,
+        MemoryUsage::Auto,
+        AllocationCreateFlags::MAPPED | AllocationCreateFlags::HOST_ACCESS_RANDOM,
+        AllocationCategory::Buffer,
+    )
+}
+
+#[track_caller]
+pub fn create_staging_buffer(allocator: &Arc<Allocator>, size: DeviceSize) -> Result<AllocatedBuffer, Error> {
+    create_allocated_buffer(
+        allocator,
+        size,
+        BufferUsageFlags::TRANSFER_SRC,
+        MemoryUsage::Auto,
+        AllocationCreateFlags::MAPPED | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+        AllocationCategory::Staging,
+    )
+}
@@ -1,28 +1,60 @@
-use ash::vk::{Extent3D, Format, Image, ImageView};
-use vk_mem::Allocation;
+use std::sync::Arc;
 
+use ash::{
+    vk::{Extent3D, Format, Image, ImageView},
+    Device,
+};
+use vk_mem::{Allocation, Allocator};
+
+use super::{
+    memory_stats::{track_free, AllocationCategory},
+    object_tracker::{track, untrack},
+};
+
+// Owns the image, its view and its memory, all released on drop.
 pub struct AllocatedImage {
     pub image: Image,
     pub image_view: ImageView,
     pub allocation: vk_mem::Allocation,
     pub extent: Extent3D,
     pub image_format: Format,
+    device: Arc<Device>,
+    allocator: Arc<Allocator>,
 }
 
 impl AllocatedImage {
+    #[track_caller]
     pub fn new(
+        device: Arc<Device>,
+        allocator: Arc<Allocator>,
         image: Image,
         image_view: ImageView,
         allocation: Allocation,
         extent: Extent3D,
         image_format: Format,
     ) -> Self {
+        track(image);
+        track(image_view);
         Self {
             image,
             image_view,
             extent,
             allocation,
             image_format,
+            device,
+            allocator,
+        }
+    }
+}
+
+impl Drop for AllocatedImage {
+    fn drop(&mut self) {
+        track_free(&self.allocator, &self.allocation, AllocationCategory::Image);
+        untrack(self.image_view);
+        untrack(self.image);
+        unsafe {
+            self.device.destroy_image_view(self.image_view, None);
+            self.allocator.destroy_image(self.image, &mut self.allocation);
         }
     }
 }
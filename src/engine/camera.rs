@@ -0,0 +1,548 @@
+use std::{f32::consts::FRAC_PI_2, mem::size_of, slice, time::Duration};
+
+use ash::vk::CompareOp;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::input::{AxisBinding, AxisSource, Binding, InputBindings, InputState};
+
+// Descriptor set the per-frame camera uniforms are bound to in every geometry pass.
+pub const CAMERA_SET: u32 = 0;
+
+// Input the cameras listen to, see `default_bindings`.
+pub const ROTATE: &str = "camera_rotate";
+pub const PAN: &str = "camera_pan";
+pub const BOOST: &str = "camera_boost";
+pub const MOVE_X: &str = "camera_move_x";
+pub const MOVE_Y: &str = "camera_move_y";
+pub const MOVE_Z: &str = "camera_move_z";
+pub const LOOK_X: &str = "camera_look_x";
+pub const LOOK_Y: &str = "camera_look_y";
+pub const ZOOM: &str = "camera_zoom";
+
+// Left drag rotates, right or middle drag pans, the wheel zooms. WASD moves the fly
+// camera with Q/E for down/up and shift to go faster.
+pub fn default_bindings() -> InputBindings {
+    InputBindings::new()
+        .with_action(ROTATE, Binding::mouse("Left"))
+        .with_action(PAN, Binding::mouse("Right"))
+        .with_action(PAN, Binding::mouse("Middle"))
+        .with_action(BOOST, Binding::key("ShiftLeft"))
+        .with_axis(MOVE_X, AxisBinding::keys(Binding::key("KeyA"), Binding::key("KeyD")))
+        .with_axis(MOVE_Y, AxisBinding::keys(Binding::key("KeyQ"), Binding::key("KeyE")))
+        .with_axis(MOVE_Z, AxisBinding::keys(Binding::key("KeyS"), Binding::key("KeyW")))
+        .with_axis(LOOK_X, AxisBinding::new(AxisSource::CursorX))
+        .with_axis(LOOK_Y, AxisBinding::new(AxisSource::CursorY))
+        .with_axis(ZOOM, AxisBinding::new(AxisSource::WheelY))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProjectionKind {
+    Perspective { fov_y: f32 },
+    // `height` is how much of the world is visible vertically
+    Orthographic { height: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Projection {
+    pub kind: ProjectionKind,
+    pub near: f32,
+    pub far: f32,
+    // Maps the near plane to depth 1 and the far plane to 0, which spreads float
+    // precision far more evenly. Depth tests have to use GREATER and clear to 0.
+    pub reverse_z: bool,
+}
+
+impl Projection {
+    pub fn perspective(fov_y: f32) -> Self {
+        Self {
+            kind: ProjectionKind::Perspective { fov_y },
+            near: 0.1,
+            far: 1000.0,
+            reverse_z: false,
+        }
+    }
+
+    pub fn orthographic(height: f32) -> Self {
+        Self {
+            kind: ProjectionKind::Orthographic { height },
+            near: -1000.0,
+            far: 1000.0,
+            reverse_z: false,
+        }
+    }
+
+    pub fn with_depth_range(mut self, near: f32, far: f32) -> Self {
+        self.near = near;
+        self.far = far;
+        self
+    }
+
+    pub fn with_reverse_z(mut self, reverse_z: bool) -> Self {
+        self.reverse_z = reverse_z;
+        self
+    }
+
+    pub fn matrix(&self, aspect: f32) -> Mat4 {
+        let (near, far) = if self.reverse_z {
+            (self.far, self.near)
+        } else {
+            (self.near, self.far)
+        };
+        let mut matrix = match self.kind {
+            ProjectionKind::Perspective { fov_y } => Mat4::perspective_rh(fov_y, aspect, near, far),
+            ProjectionKind::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * aspect;
+                Mat4::orthographic_rh(-half_width, half_width, -half_height, half_height, near, far)
+            }
+        };
+        // Vulkan's clip space has y pointing down
+        matrix.y_axis.y = -matrix.y_axis.y;
+        matrix
+    }
+}
+
+// What the depth buffer is cleared to and how depth is compared for `reverse_z`, the
+// clear value is the far plane and the test keeps whatever is nearer.
+pub fn depth_clear_value(reverse_z: bool) -> f32 {
+    if reverse_z {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+pub fn depth_compare_op(reverse_z: bool) -> CompareOp {
+    if reverse_z {
+        CompareOp::GREATER_OR_EQUAL
+    } else {
+        CompareOp::LESS_OR_EQUAL
+    }
+}
+
+// What the shaders see at set CAMERA_SET, binding 0. Laid out for std140:
+//
+// layout(set = 0, binding = 0) uniform Camera {
+//     mat4 view; mat4 projection; mat4 viewProjection; mat4 inverseViewProjection;
+//     vec4 position; vec4 viewport; // width, height, near, far
+// } camera;
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraUniforms {
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_projection: Mat4,
+    pub inverse_view_projection: Mat4,
+    pub position: Vec4,
+    pub viewport: Vec4,
+}
+
+impl CameraUniforms {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    }
+}
+
+pub trait Camera {
+    // Called once a frame with this frame's input, `viewport` is the draw size in pixels.
+    fn update(&mut self, input: &InputState, delta: Duration, viewport: Vec2);
+    fn view(&self) -> Mat4;
+    fn projection(&self) -> Projection;
+    fn position(&self) -> Vec3;
+
+    fn uniforms(&self, viewport: Vec2) -> CameraUniforms {
+        let projection = self.projection();
+        let view = self.view();
+        let projection_matrix = projection.matrix(viewport.x / viewport.y.max(1.0));
+        let view_projection = projection_matrix * view;
+        CameraUniforms {
+            view,
+            projection: projection_matrix,
+            view_projection,
+            inverse_view_projection: view_projection.inverse(),
+            position: self.position().extend(1.0),
+            viewport: Vec4::new(viewport.x, viewport.y, projection.near, projection.far),
+        }
+    }
+}
+
+// Just short of straight up or down, where the view matrix would flip
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        -yaw.cos() * pitch.cos(),
+    )
+}
+
+// Circles `target` at `distance`. Rotate drags around it, pan drags move the target
+// and the wheel moves closer or further away.
+#[derive(Clone, Debug)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub projection: Projection,
+    // Radians per pixel of cursor movement
+    pub sensitivity: f32,
+    pub min_distance: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: Vec3::ZERO,
+            distance: 5.0,
+            yaw: 0.0,
+            pitch: -0.4,
+            projection: Projection::perspective(60f32.to_radians()),
+            sensitivity: 0.005,
+            min_distance: 0.05,
+        }
+    }
+}
+
+impl Camera for OrbitCamera {
+    fn update(&mut self, input: &InputState, _delta: Duration, viewport: Vec2) {
+        let look = Vec2::new(input.axis(LOOK_X), input.axis(LOOK_Y));
+        if input.held(ROTATE) {
+            self.yaw += look.x * self.sensitivity;
+            self.pitch = (self.pitch - look.y * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        } else if input.held(PAN) {
+            // Keep the point under the cursor under the cursor
+            let forward = direction(self.yaw, self.pitch);
+            let right = forward.cross(Vec3::Y).normalize();
+            let up = right.cross(forward);
+            let world_per_pixel = match self.projection.kind {
+                ProjectionKind::Perspective { fov_y } => {
+                    2.0 * self.distance * (fov_y / 2.0).tan() / viewport.y.max(1.0)
+                }
+                ProjectionKind::Orthographic { height } => height / viewport.y.max(1.0),
+            };
+            self.target += (up * look.y - right * look.x) * world_per_pixel;
+        }
+        let zoom = input.axis(ZOOM);
+        if zoom != 0.0 {
+            self.distance = (self.distance * 0.9f32.powf(zoom)).max(self.min_distance);
+        }
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position(), self.target, Vec3::Y)
+    }
+
+    fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    fn position(&self) -> Vec3 {
+        self.target - direction(self.yaw, self.pitch) * self.distance
+    }
+}
+
+// First person: rotate drags look around, the move axes fly along the view direction.
+#[derive(Clone, Debug)]
+pub struct FlyCamera {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub projection: Projection,
+    // World units per second, multiplied by `boost` while BOOST is held
+    pub speed: f32,
+    pub boost: f32,
+    pub sensitivity: f32,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0.0, 1.0, 5.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            projection: Projection::perspective(70f32.to_radians()),
+            speed: 3.0,
+            boost: 4.0,
+            sensitivity: 0.003,
+        }
+    }
+}
+
+impl Camera for FlyCamera {
+    fn update(&mut self, input: &InputState, delta: Duration, _viewport: Vec2) {
+        if input.held(ROTATE) {
+            self.yaw += input.axis(LOOK_X) * self.sensitivity;
+            self.pitch = (self.pitch - input.axis(LOOK_Y) * self.sensitivity)
+                .clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        let forward = direction(self.yaw, self.pitch);
+        let right = forward.cross(Vec3::Y).normalize();
+        let movement = right * input.axis(MOVE_X)
+            + Vec3::Y * input.axis(MOVE_Y)
+            + forward * input.axis(MOVE_Z);
+        let speed = if input.held(BOOST) {
+            self.speed * self.boost
+        } else {
+            self.speed
+        };
+        self.position += movement.normalize_or_zero() * speed * delta.as_secs_f32();
+        // The wheel changes how fast we fly
+        let zoom = input.axis(ZOOM);
+        if zoom != 0.0 {
+            self.speed = (self.speed * 1.1f32.powf(zoom)).max(0.01);
+        }
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, direction(self.yaw, self.pitch), Vec3::Y)
+    }
+
+    fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+// Looks down -z at the xy plane. Rotate or pan drags move it, the wheel zooms
+// towards the cursor.
+#[derive(Clone, Debug)]
+pub struct PanZoomCamera {
+    pub center: Vec2,
+    // World units visible vertically
+    pub height: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+}
+
+impl Default for PanZoomCamera {
+    fn default() -> Self {
+        Self {
+            center: Vec2::ZERO,
+            height: 10.0,
+            min_height: 0.01,
+            max_height: 10_000.0,
+        }
+    }
+}
+
+impl Camera for PanZoomCamera {
+    fn update(&mut self, input: &InputState, _delta: Duration, viewport: Vec2) {
+        let world_per_pixel = self.height / viewport.y.max(1.0);
+        if input.held(ROTATE) || input.held(PAN) {
+            let drag = Vec2::new(input.axis(LOOK_X), input.axis(LOOK_Y));
+            self.center += Vec2::new(-drag.x, drag.y) * world_per_pixel;
+        }
+        let zoom = input.axis(ZOOM);
+        if zoom != 0.0 {
+            let height = (self.height * 0.9f32.powf(zoom)).clamp(self.min_height, self.max_height);
+            if let Some((x, y)) = input.cursor_position() {
+                let offset = Vec2::new(x - viewport.x / 2.0, viewport.y / 2.0 - y);
+                let cursor = self.center + offset * world_per_pixel;
+                self.center = cursor - offset * height / viewport.y.max(1.0);
+            }
+            self.height = height;
+        }
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::from_translation(-self.center.extend(0.0))
+    }
+
+    fn projection(&self) -> Projection {
+        Projection::orthographic(self.height)
+    }
+
+    fn position(&self) -> Vec3 {
+        self.center.extend(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::{
+        dpi::PhysicalPosition,
+        event::{DeviceId, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
+    };
+
+    use super::*;
+
+    const VIEWPORT: Vec2 = Vec2::new(800.0, 600.0);
+    const FRAME: Duration = Duration::from_millis(500);
+
+    fn send(input: &mut InputState, event: WindowEvent) {
+        input.on_window_event(&event, false, false);
+    }
+
+    fn button(input: &mut InputState, button: MouseButton, state: ElementState) {
+        let device_id = DeviceId::dummy();
+        send(input, WindowEvent::MouseInput { device_id, state, button });
+    }
+
+    fn cursor_to(input: &mut InputState, x: f64, y: f64) {
+        let position = PhysicalPosition::new(x, y);
+        send(input, WindowEvent::CursorMoved { device_id: DeviceId::dummy(), position });
+    }
+
+    fn scroll(input: &mut InputState, notches: f32) {
+        send(
+            input,
+            WindowEvent::MouseWheel {
+                device_id: DeviceId::dummy(),
+                delta: MouseScrollDelta::LineDelta(0.0, notches),
+                phase: TouchPhase::Moved,
+            },
+        );
+    }
+
+    // Moves the cursor by (dx, dy) with `held` down, leaving the button down.
+    fn drag(input: &mut InputState, held: MouseButton, dx: f64, dy: f64) {
+        cursor_to(input, 400.0, 300.0);
+        button(input, held, ElementState::Pressed);
+        cursor_to(input, 400.0 + dx, 300.0 + dy);
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{a} != {b}");
+    }
+
+    fn depth(matrix: Mat4, distance: f32) -> f32 {
+        let clip = matrix * Vec4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn reverse_z_swaps_the_near_and_far_depths() {
+        for kind in [Projection::perspective(1.0), Projection::orthographic(10.0)] {
+            for reverse_z in [false, true] {
+                let projection = kind.with_depth_range(0.5, 50.0).with_reverse_z(reverse_z);
+                let matrix = projection.matrix(VIEWPORT.x / VIEWPORT.y);
+                let (near, far) = (depth(matrix, 0.5), depth(matrix, 50.0));
+                let expected = if reverse_z { (1.0, 0.0) } else { (0.0, 1.0) };
+                assert!(
+                    (near - expected.0).abs() < 1e-5 && (far - expected.1).abs() < 1e-5,
+                    "{projection:?} maps near and far to {near} and {far}"
+                );
+                // Clearing to the far plane and keeping the nearer fragment
+                assert!((depth_clear_value(reverse_z) - far).abs() < 1e-5);
+                let nearer_passes = match depth_compare_op(reverse_z) {
+                    CompareOp::GREATER_OR_EQUAL => near > far,
+                    CompareOp::LESS_OR_EQUAL => near < far,
+                    op => panic!("unexpected depth test {op:?}"),
+                };
+                assert!(nearer_passes, "{projection:?} keeps the farther fragment");
+            }
+        }
+    }
+
+    #[test]
+    fn orbit_pitch_and_distance_stay_in_range() {
+        let mut input = InputState::new(default_bindings());
+        let mut camera = OrbitCamera::default();
+        drag(&mut input, MouseButton::Left, 100.0, -100_000.0);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_eq!(camera.yaw, 100.0 * camera.sensitivity);
+        assert_eq!(camera.pitch, MAX_PITCH);
+
+        input.end_frame();
+        cursor_to(&mut input, 400.0, 300.0 + 200_000.0);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_eq!(camera.pitch, -MAX_PITCH);
+        button(&mut input, MouseButton::Left, ElementState::Released);
+
+        input.end_frame();
+        scroll(&mut input, 1000.0);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_eq!(camera.distance, camera.min_distance);
+
+        input.end_frame();
+        scroll(&mut input, -2.0);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert!((camera.distance - camera.min_distance / 0.81).abs() < 1e-5);
+        // Still looking at the target from `distance` away
+        let offset = camera.target - camera.position();
+        assert!((offset.length() - camera.distance).abs() < 1e-5);
+    }
+
+    #[test]
+    fn fly_moves_along_the_view_basis() {
+        let bindings = InputBindings::new()
+            .with_action(BOOST, Binding::mouse("Middle"))
+            .with_axis(MOVE_X, AxisBinding::keys(Binding::mouse("Left"), Binding::mouse("Right")))
+            .with_axis(MOVE_Z, AxisBinding::keys(Binding::mouse("Back"), Binding::mouse("Forward")));
+        let mut input = InputState::new(bindings);
+        // Facing +x, so right is +z
+        let mut camera = FlyCamera {
+            position: Vec3::ZERO,
+            yaw: FRAC_PI_2,
+            speed: 2.0,
+            ..Default::default()
+        };
+        assert_near(camera.view().transform_vector3(Vec3::X), Vec3::NEG_Z);
+
+        button(&mut input, MouseButton::Forward, ElementState::Pressed);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_near(camera.position, Vec3::X);
+
+        button(&mut input, MouseButton::Forward, ElementState::Released);
+        button(&mut input, MouseButton::Right, ElementState::Pressed);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_near(camera.position, Vec3::new(1.0, 0.0, 1.0));
+
+        button(&mut input, MouseButton::Middle, ElementState::Pressed);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_near(camera.position, Vec3::new(1.0, 0.0, 1.0 + camera.boost));
+
+        // Diagonals are no faster than straight lines
+        button(&mut input, MouseButton::Middle, ElementState::Released);
+        button(&mut input, MouseButton::Back, ElementState::Pressed);
+        let before = camera.position;
+        camera.update(&input, FRAME, VIEWPORT);
+        let step = camera.position - before;
+        assert!((step.length() - 1.0).abs() < 1e-5);
+        assert_near(step.normalize(), Vec3::new(-1.0, 0.0, 1.0).normalize());
+    }
+
+    // Where the pixel at `cursor` lands in the world, through the camera's matrices.
+    fn world_under(camera: &PanZoomCamera, cursor: Vec2) -> Vec3 {
+        let ndc = cursor / VIEWPORT * 2.0 - 1.0;
+        let uniforms = camera.uniforms(VIEWPORT);
+        uniforms
+            .inverse_view_projection
+            .project_point3(ndc.extend(0.5))
+    }
+
+    #[test]
+    fn pan_zoom_keeps_the_point_under_the_cursor() {
+        let mut input = InputState::new(default_bindings());
+        let mut camera = PanZoomCamera {
+            center: Vec2::new(3.0, -2.0),
+            ..Default::default()
+        };
+        let cursor = Vec2::new(600.0, 150.0);
+        cursor_to(&mut input, cursor.x as f64, cursor.y as f64);
+        let before = world_under(&camera, cursor);
+        for notches in [3.0, -5.0] {
+            input.end_frame();
+            scroll(&mut input, notches);
+            camera.update(&input, FRAME, VIEWPORT);
+            let after = world_under(&camera, cursor);
+            assert_near(before.truncate().extend(0.0), after.truncate().extend(0.0));
+        }
+        assert!((camera.height - 10.0 / 0.9f32.powi(2)).abs() < 1e-4);
+
+        input.end_frame();
+        scroll(&mut input, 1000.0);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_eq!(camera.height, camera.min_height);
+        input.end_frame();
+        scroll(&mut input, -1000.0);
+        camera.update(&input, FRAME, VIEWPORT);
+        assert_eq!(camera.height, camera.max_height);
+    }
+}
@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+
+// A long stall (a breakpoint, dragging the window, a suspend) would otherwise queue up
+// hundreds of fixed steps that all have to run before the next frame.
+const MAX_FRAME_DELTA: Duration = Duration::from_millis(250);
+
+pub const DEFAULT_FIXED_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Clone, Copy, Debug)]
+enum Source {
+    Realtime { last: Option<Instant> },
+    // Every tick advances by exactly `step`, so frame N always sees the same time
+    Manual { step: Duration },
+}
+
+// Snapshot of the clock for the current frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameTime {
+    pub delta: Duration,
+    pub total: Duration,
+    pub frame: u64,
+}
+
+impl FrameTime {
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub fn total_seconds(&self) -> f32 {
+        self.total.as_secs_f32()
+    }
+}
+
+// Drives the frame loop. `tick` once per rendered frame, then drain `fixed_step` to run
+// the simulation at a rate independent of how fast frames are drawn.
+#[derive(Clone, Debug)]
+pub struct FrameClock {
+    source: Source,
+    fixed_step: Duration,
+    accumulator: Duration,
+    time: FrameTime,
+    started: bool,
+}
+
+impl FrameClock {
+    pub fn realtime(fixed_step: Duration) -> Self {
+        Self::with_source(Source::Realtime { last: None }, fixed_step)
+    }
+
+    pub fn manual(step: Duration, fixed_step: Duration) -> Self {
+        Self::with_source(Source::Manual { step }, fixed_step)
+    }
+
+    fn with_source(source: Source, fixed_step: Duration) -> Self {
+        assert!(!fixed_step.is_zero(), "fixed step must be non-zero");
+        Self {
+            source,
+            fixed_step,
+            accumulator: Duration::ZERO,
+            time: FrameTime::default(),
+            started: false,
+        }
+    }
+
+    pub fn is_manual(&self) -> bool {
+        matches!(self.source, Source::Manual { .. })
+    }
+
+    // Starts a new frame. The first tick is frame 0 at time 0 with a zero delta, in
+    // manual mode frame N is then always at N * step.
+    pub fn tick(&mut self) -> FrameTime {
+        let delta = match &mut self.source {
+            Source::Realtime { last } => {
+                let now = Instant::now();
+                let delta = last.map(|last| now - last).unwrap_or_default();
+                *last = Some(now);
+                delta.min(MAX_FRAME_DELTA)
+            }
+            Source::Manual { step } if self.started => *step,
+            Source::Manual { .. } => Duration::ZERO,
+        };
+        if self.started {
+            self.time.frame += 1;
+        }
+        self.started = true;
+        self.time.delta = delta;
+        self.time.total += delta;
+        self.accumulator += delta;
+        self.time
+    }
+
+    // True while a whole fixed step is owed, consuming it. Call in a loop after `tick`.
+    pub fn fixed_step(&mut self) -> bool {
+        if self.accumulator >= self.fixed_step {
+            self.accumulator -= self.fixed_step;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn fixed_step_duration(&self) -> Duration {
+        self.fixed_step
+    }
+
+    // How far into the next fixed step the frame is, for interpolating simulation state.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.fixed_step.as_secs_f32()
+    }
+
+    pub fn time(&self) -> FrameTime {
+        self.time
+    }
+
+    // Forget the last wall clock reading, so the time spent suspended doesn't show up
+    // as one long frame.
+    pub fn resync(&mut self) {
+        if let Source::Realtime { last } = &mut self.source {
+            *last = None;
+        }
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::realtime(DEFAULT_FIXED_STEP)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn manual_frame_n_is_at_n_steps() {
+        let mut clock = FrameClock::manual(STEP, STEP);
+        let first = clock.tick();
+        assert_eq!(first, FrameTime::default());
+        for frame in 1..=5u32 {
+            let time = clock.tick();
+            assert_eq!(time.frame, frame as u64);
+            assert_eq!(time.delta, STEP);
+            assert_eq!(time.total, STEP * frame);
+        }
+        assert_eq!(clock.time().total, STEP * 5);
+    }
+
+    #[test]
+    fn manual_alpha_is_the_leftover_over_the_fixed_step() {
+        let fixed_step = STEP * 5 / 2;
+        let mut clock = FrameClock::manual(STEP, fixed_step);
+        let mut steps = 0u32;
+        for frame in 0..20u32 {
+            let time = clock.tick();
+            assert_eq!(time.delta, if frame == 0 { Duration::ZERO } else { STEP });
+            assert_eq!(time.total, STEP * frame);
+            while clock.fixed_step() {
+                steps += 1;
+            }
+            let leftover = time.total - fixed_step * steps;
+            assert!(leftover < fixed_step, "frame {frame} left a whole step undrained");
+            assert_eq!(
+                clock.alpha(),
+                leftover.as_secs_f32() / fixed_step.as_secs_f32()
+            );
+        }
+        assert_eq!(steps, 7);
+    }
+
+    #[test]
+    fn fixed_steps_drain_what_the_frame_owes() {
+        let mut clock = FrameClock::manual(STEP * 5 / 2, STEP);
+        let mut steps_per_frame = Vec::new();
+        for _ in 0..4 {
+            clock.tick();
+            let mut steps = 0;
+            while clock.fixed_step() {
+                steps += 1;
+            }
+            steps_per_frame.push(steps);
+        }
+        // Each frame after the first owes 25ms, the 5ms left over carries into the next
+        assert_eq!(steps_per_frame, [0, 2, 3, 2]);
+        assert_eq!(clock.alpha(), 0.5);
+    }
+
+    #[test]
+    fn resync_leaves_manual_time_alone() {
+        let mut clock = FrameClock::manual(STEP, STEP);
+        clock.tick();
+        clock.resync();
+        assert_eq!(clock.tick().delta, STEP);
+        assert!(clock.is_manual());
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed step must be non-zero")]
+    fn zero_fixed_step_is_rejected() {
+        FrameClock::manual(STEP, Duration::ZERO);
+    }
+}
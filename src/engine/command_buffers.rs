@@ -1,11 +1,16 @@
+use std::sync::Arc;
+
 use ash::{vk::{CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferSubmitInfo, CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo}, Device};
 
-pub fn create_command_pool(device: &Device, queue_family_index: u32) -> CommandPool {
+use super::handles::Owned;
+
+pub fn create_command_pool(device: &Arc<Device>, queue_family_index: u32) -> Owned<CommandPool> {
     let create_info = CommandPoolCreateInfo::default()
         .queue_family_index(queue_family_index)
         .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
 
-    unsafe { device.create_command_pool(&create_info, None).unwrap() }
+    let command_pool = unsafe { device.create_command_pool(&create_info, None).unwrap() };
+    Owned::new(device.clone(), command_pool)
 }
 
 pub fn allocate_command_buffer(device: &Device, command_pool: CommandPool) -> CommandBuffer {
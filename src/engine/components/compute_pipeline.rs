@@ -1,45 +1,177 @@
-use std::{io::Error, sync::Arc};
+use std::{io::Error, mem::size_of, slice, sync::Arc};
 
 use ash::{
     vk::{
-        ComputePipelineCreateInfo, DescriptorSetLayout, Pipeline, PipelineCache, PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateFlags, PipelineShaderStageCreateInfo, ShaderStageFlags
+        ComputePipelineCreateInfo, DescriptorSetLayout, Pipeline, PipelineCache, PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateFlags, PushConstantRange, PipelineShaderStageCreateInfo, ShaderStageFlags
     },
     Device,
 };
 use log::debug;
 
-use crate::engine::deletion_queue::DeletionQueue;
+use crate::engine::handles::Owned;
+
+use super::shader_registry::ShaderRegistry;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComputePushConstants {
+    pub data1: [f32; 4],
+    pub data2: [f32; 4],
+    pub data3: [f32; 4],
+    pub data4: [f32; 4],
+    pub time: f32,
+    pub delta_time: f32,
+    pub frame: u32,
+}
+
+impl Default for ComputePushConstants {
+    fn default() -> Self {
+        Self {
+            data1: [1.0, 0.0, 0.0, 1.0],
+            data2: [0.0, 0.0, 1.0, 1.0],
+            data3: [0.0; 4],
+            data4: [0.0; 4],
+            time: 0.0,
+            delta_time: 0.0,
+            frame: 0,
+        }
+    }
+}
+
+impl ComputePushConstants {
+    pub fn push_constant_range() -> PushConstantRange {
+        PushConstantRange::default()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(size_of::<Self>() as u32)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    }
+}
+
+pub struct ComputeEffect {
+    pub name: String,
+    pub pipeline: Owned<Pipeline>,
+    pub layout: Arc<Owned<PipelineLayout>>,
+    pub default_parameters: ComputePushConstants,
+}
+
+fn background_effects() -> Vec<(&'static str, ComputePushConstants)> {
+    vec![
+        ("gradient", ComputePushConstants::default()),
+        (
+            "sky",
+            ComputePushConstants {
+                data1: [0.1, 0.2, 0.4, 0.97],
+                data2: [1.0, 0.0, 0.0, 0.0],
+                ..Default::default()
+            },
+        ),
+        (
+            "noise",
+            ComputePushConstants {
+                data1: [0.05, 0.05, 0.1, 1.0],
+                data2: [0.9, 0.7, 0.4, 1.0],
+                data3: [4.0, 0.1, 0.0, 0.0],
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+pub fn create_compute_pipeline_layout(
+    device: Arc<Device>,
+    layouts: &[DescriptorSetLayout],
+    push_constant_ranges: &[PushConstantRange],
+) -> Owned<PipelineLayout> {
+    let create_info = PipelineLayoutCreateInfo::default()
+        .set_layouts(layouts)
+        .push_constant_ranges(push_constant_ranges);
+    let pipeline_layout = unsafe { device.create_pipeline_layout(&create_info, None).unwrap() };
+    Owned::new(device, pipeline_layout)
+}
 
-use super::util::load_shader_module;
 pub fn init_background_pipelines(
     device: Arc<Device>,
     layouts: &[DescriptorSetLayout],
-    deletion_queue: &mut DeletionQueue,
-) -> Result<(PipelineLayout, Pipeline), Error> {
+    push_constant_ranges: &[PushConstantRange],
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
+) -> Result<Vec<ComputeEffect>, Error> {
 
-    let create_info = PipelineLayoutCreateInfo::default().set_layouts(layouts);
-    let pipeline_layout = unsafe { device.create_pipeline_layout(&create_info, None).unwrap() };
-    let shader_module = load_shader_module("shaders/shader.spv", device.clone()).unwrap();
+    // Shared by every effect, destroyed with the last one
+    let pipeline_layout = Arc::new(create_compute_pipeline_layout(
+        device.clone(),
+        layouts,
+        push_constant_ranges,
+    ));
+
+    background_effects()
+        .into_iter()
+        .map(|(name, default_parameters)| {
+            create_compute_effect(
+                device.clone(),
+                name,
+                pipeline_layout.clone(),
+                default_parameters,
+                shader_registry,
+                pipeline_cache,
+            )
+        })
+        .collect()
+}
+
+pub fn create_compute_effect(
+    device: Arc<Device>,
+    name: &str,
+    pipeline_layout: Arc<Owned<PipelineLayout>>,
+    default_parameters: ComputePushConstants,
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
+) -> Result<ComputeEffect, Error> {
+    let pipeline = create_compute_pipeline(
+        device,
+        name,
+        **pipeline_layout,
+        shader_registry,
+        pipeline_cache,
+    )?;
+    Ok(ComputeEffect {
+        name: name.to_string(),
+        pipeline,
+        layout: pipeline_layout,
+        default_parameters,
+    })
+}
+
+// Any compute shader from the registry, `name` is its registry name.
+pub fn create_compute_pipeline(
+    device: Arc<Device>,
+    name: &str,
+    pipeline_layout: PipelineLayout,
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
+) -> Result<Owned<Pipeline>, Error> {
+    // Only needed while the pipeline is created
+    let shader_module = Owned::new(
+        device.clone(),
+        shader_registry.load_module(name, device.clone())?,
+    );
     let shader_stage_info = PipelineShaderStageCreateInfo::default()
-        .module(shader_module)
+        .module(*shader_module)
         .name(c"main")
         .stage(ShaderStageFlags::COMPUTE);
 
     let pipeline_create_info = vec![ComputePipelineCreateInfo::default()
         .stage(shader_stage_info)
         .layout(pipeline_layout)];
-    let device_clone = device.clone();
-    let device_clone2 = device.clone();
-    let device_clone3 = device.clone();
     unsafe {
-        deletion_queue.enqueue(move || device_clone.destroy_shader_module(shader_module, None));
-        deletion_queue.enqueue(move || device_clone2.destroy_pipeline_layout(pipeline_layout, None));
-        debug!("BEFORE CREATION");
+        debug!("Creating compute pipeline {name}");
         let pipeline = *device
-            .create_compute_pipelines(PipelineCache::null(), &pipeline_create_info, None)
+            .create_compute_pipelines(pipeline_cache, &pipeline_create_info, None)
             .unwrap().get(0).unwrap();
-        debug!("AFTER CREATION");
-        deletion_queue.enqueue(move|| device_clone3.destroy_pipeline(pipeline,None));
-        Ok((pipeline_layout, pipeline))
+        Ok(Owned::new(device, pipeline))
     }
 }
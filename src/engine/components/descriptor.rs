@@ -2,19 +2,19 @@ use std::{io::Error, sync::Arc};
 
 use ash::{
     vk::{
-        DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo,
+        Buffer, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo,
         DescriptorPoolResetFlags, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo,
         DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags,
-        DescriptorSetLayoutCreateInfo, DescriptorType, ImageLayout, ShaderStageFlags,
+        DescriptorSetLayoutCreateInfo, DescriptorType, DeviceSize, ImageLayout, ShaderStageFlags,
         WriteDescriptorSet,
     },
     Device,
 };
 
-use crate::engine::{allocated_image::AllocatedImage, deletion_queue::DeletionQueue};
+use crate::engine::{allocated_image::AllocatedImage, handles::Owned};
 
 pub struct DescriptorAllocator {
-    pool: DescriptorPool,
+    pool: Owned<DescriptorPool>,
 }
 
 pub struct PoolSizeRatio {
@@ -22,6 +22,15 @@ pub struct PoolSizeRatio {
     ratio: f32,
 }
 
+impl PoolSizeRatio {
+    pub fn new(descriptor_type: DescriptorType, ratio: f32) -> Self {
+        Self {
+            descriptor_type,
+            ratio,
+        }
+    }
+}
+
 pub struct DescriptorLayoutBuilder<'a> {
     bindings: Vec<DescriptorSetLayoutBinding<'a>>,
 }
@@ -29,22 +38,26 @@ pub struct DescriptorLayoutBuilder<'a> {
 pub fn init_descriptors(
     device: Arc<Device>,
     allocated_image: Arc<AllocatedImage>,
-    deletion_queue: &mut DeletionQueue,
-) -> Result<(Arc<DescriptorAllocator>, DescriptorSetLayout, DescriptorSet), Error> {
+) -> Result<(Arc<DescriptorAllocator>, Owned<DescriptorSetLayout>, DescriptorSet), Error> {
     let mut pool_sizes: Vec<PoolSizeRatio> = Vec::new();
     pool_sizes.push(PoolSizeRatio {
         descriptor_type: DescriptorType::STORAGE_IMAGE,
         ratio: 1.0
     });
-    let descriptor_allocator = Arc::new(DescriptorAllocator::new(device.clone(), 10, pool_sizes));
+    let descriptor_allocator = Arc::new(DescriptorAllocator::new(
+        device.clone(),
+        10,
+        pool_sizes,
+        DescriptorPoolCreateFlags::empty(),
+    ));
     let mut descriptor_layout_builder = DescriptorLayoutBuilder::new();
     descriptor_layout_builder.add_binding(0, DescriptorType::STORAGE_IMAGE);
-    let layout = Arc::new(descriptor_layout_builder.build(
+    let layout = descriptor_layout_builder.build(
         device.clone(),
         ShaderStageFlags::COMPUTE,
         DescriptorSetLayoutCreateFlags::empty(),
-    ));
-    let descriptor_set = descriptor_allocator.allocate(device.clone(), &[*layout]);
+    );
+    let descriptor_set = descriptor_allocator.allocate(&device, &[*layout]);
     let descriptor_image_info = vec![DescriptorImageInfo::default()
         .image_layout(ImageLayout::GENERAL)
         .image_view(allocated_image.image_view)];
@@ -57,18 +70,50 @@ pub fn init_descriptors(
 
     unsafe { device.update_descriptor_sets(&[write_descriptor_set], &[]) };
 
-    let device_clone = device.clone();
-    let device_clone2 = device.clone();
-    let descriptor_alloc_clone = descriptor_allocator.clone();
+    Ok((descriptor_allocator, layout, descriptor_set))
+}
 
-    let layout_clone = layout.clone();
-    unsafe {
-        deletion_queue.enqueue(move || descriptor_alloc_clone.destroy_pool(&device_clone));
-        deletion_queue
-            .enqueue(move || device_clone2.destroy_descriptor_set_layout(*layout_clone, None));
-    }
+pub fn write_uniform_buffer(
+    device: &Device,
+    descriptor_set: DescriptorSet,
+    binding: u32,
+    buffer: Buffer,
+    range: DeviceSize,
+) {
+    let buffer_info = vec![DescriptorBufferInfo::default()
+        .buffer(buffer)
+        .offset(0)
+        .range(range)];
+    let write_descriptor_set = WriteDescriptorSet::default()
+        .dst_binding(binding)
+        .descriptor_count(1)
+        .dst_set(descriptor_set)
+        .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+        .buffer_info(&buffer_info);
+
+    unsafe { device.update_descriptor_sets(&[write_descriptor_set], &[]) };
+}
+
+pub fn write_storage_buffer(
+    device: &Device,
+    descriptor_set: DescriptorSet,
+    binding: u32,
+    buffer: Buffer,
+    offset: DeviceSize,
+    range: DeviceSize,
+) {
+    let buffer_info = vec![DescriptorBufferInfo::default()
+        .buffer(buffer)
+        .offset(offset)
+        .range(range)];
+    let write_descriptor_set = WriteDescriptorSet::default()
+        .dst_binding(binding)
+        .descriptor_count(1)
+        .dst_set(descriptor_set)
+        .descriptor_type(DescriptorType::STORAGE_BUFFER)
+        .buffer_info(&buffer_info);
 
-    Ok((descriptor_allocator, *layout, descriptor_set))
+    unsafe { device.update_descriptor_sets(&[write_descriptor_set], &[]) };
 }
 
 impl DescriptorAllocator {
@@ -76,6 +121,7 @@ impl DescriptorAllocator {
         device: Arc<Device>,
         max_sets: u32,
         pool_sizes: Vec<PoolSizeRatio>,
+        flags: DescriptorPoolCreateFlags,
     ) -> DescriptorAllocator {
         let mut descriptor_pool_sizes: Vec<DescriptorPoolSize> = vec![];
         for pool_size in pool_sizes {
@@ -88,28 +134,38 @@ impl DescriptorAllocator {
         let create_info = DescriptorPoolCreateInfo::default()
             .max_sets(max_sets)
             .pool_sizes(&descriptor_pool_sizes)
-            .flags(DescriptorPoolCreateFlags::empty());
+            .flags(flags);
 
+        let pool = unsafe { device.create_descriptor_pool(&create_info, None).unwrap() };
         Self {
-            pool: unsafe { device.create_descriptor_pool(&create_info, None).unwrap() },
+            pool: Owned::new(device, pool),
         }
     }
 
     pub fn reset_descriptors(&self, device: &Device) {
         unsafe {
             device
-                .reset_descriptor_pool(self.pool, DescriptorPoolResetFlags::empty())
+                .reset_descriptor_pool(*self.pool, DescriptorPoolResetFlags::empty())
                 .unwrap()
         }
     }
 
-    pub fn destroy_pool(&self, device: &Device) {
-        unsafe { device.destroy_descriptor_pool(self.pool, None) }
+    pub fn pool(&self) -> DescriptorPool {
+        *self.pool
     }
 
-    pub fn allocate(&self, device: Arc<Device>, layouts: &[DescriptorSetLayout]) -> DescriptorSet {
+    // Only valid for pools created with DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET
+    pub fn free(&self, device: &Device, descriptor_sets: &[DescriptorSet]) {
+        unsafe {
+            device
+                .free_descriptor_sets(*self.pool, descriptor_sets)
+                .unwrap()
+        }
+    }
+
+    pub fn allocate(&self, device: &Device, layouts: &[DescriptorSetLayout]) -> DescriptorSet {
         let mut allocate_info = DescriptorSetAllocateInfo::default()
-            .descriptor_pool(self.pool)
+            .descriptor_pool(*self.pool)
             .set_layouts(layouts);
         allocate_info.descriptor_set_count = 1;
 
@@ -148,7 +204,7 @@ impl<'a> DescriptorLayoutBuilder<'a> {
         device: Arc<Device>,
         shader_stages: ShaderStageFlags,
         flags: DescriptorSetLayoutCreateFlags,
-    ) -> DescriptorSetLayout {
+    ) -> Owned<DescriptorSetLayout> {
         for binding in &mut self.bindings {
             binding.stage_flags = binding.stage_flags | shader_stages
         }
@@ -157,10 +213,11 @@ impl<'a> DescriptorLayoutBuilder<'a> {
             .bindings(&self.bindings)
             .flags(flags);
 
-        unsafe {
+        let layout = unsafe {
             device
                 .create_descriptor_set_layout(&descriptor_set_create_info, None)
                 .unwrap()
-        }
+        };
+        Owned::new(device, layout)
     }
 }
@@ -1,4 +1,4 @@
-use std::io::Error;
+use std::ffi::CStr;
 
 use ash::{
     khr::surface,
@@ -12,6 +12,10 @@ use winit::window::Window;
 
 use super::{swapchain_support_details::SwapchainSupportDetails, QueueFamilyIndices};
 
+pub fn device_extensions() -> Vec<&'static CStr> {
+    vec![KHR_SWAPCHAIN_NAME, KHR_PORTABILITY_SUBSET_NAME]
+}
+
 pub fn create_device(
     instance: &Instance,
     surface_instance: &surface::Instance,
@@ -28,10 +32,10 @@ pub fn create_device(
                 surface,
             );
             let features = unsafe { instance.get_physical_device_features(physical_device) };
-            let extensions = vec![
-                KHR_SWAPCHAIN_NAME.as_ptr(),
-                KHR_PORTABILITY_SUBSET_NAME.as_ptr(),
-            ];
+            let extensions = device_extensions()
+                .iter()
+                .map(|extension| extension.as_ptr())
+                .collect::<Vec<_>>();
 
             let device_queue_create_infos = vec![DeviceQueueCreateInfo::default()
                 .queue_family_index(indices.graphics_q_idx.unwrap())
@@ -1,22 +1,30 @@
-use std::{io::Error, sync::Arc};
+use std::{
+    io::Error,
+    mem::{offset_of, size_of},
+    sync::Arc,
+};
 
 use ash::{
     vk::{
-        BlendFactor, BlendOp, ColorComponentFlags, CullModeFlags, DynamicState, Extent2D, FrontFace, GraphicsPipelineCreateInfo, LogicOp, Offset2D, Pipeline, PipelineCache, PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo, PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateFlags, PipelineShaderStageCreateInfo, PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, Rect2D, RenderPass, SampleCountFlags, ShaderModule, ShaderModuleCreateFlags, ShaderModuleCreateInfo, ShaderStageFlags, Viewport
+        BlendFactor, BlendOp, ColorComponentFlags, CullModeFlags, DescriptorSetLayout, DynamicState, Extent2D, Format, FrontFace, GraphicsPipelineCreateInfo, LogicOp, Offset2D, Pipeline, PipelineCache, PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateFlags, PipelineShaderStageCreateInfo, PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, PushConstantRange, Rect2D, RenderPass, SampleCountFlags, ShaderModule, ShaderModuleCreateFlags, ShaderModuleCreateInfo, ShaderStageFlags, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate, Viewport
     },
     Device,
 };
 
-use super::util::load_shader_module;
+use crate::engine::{camera::depth_compare_op, handles::Owned, mesh::Vertex};
+
+use super::shader_registry::ShaderRegistry;
 
 pub fn create_graphics_pipeline(
     device: Arc<Device>,
     render_pass: &RenderPass,
     extent: &Extent2D,
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
 ) -> Result<Vec<Pipeline>, Error> {
     let dynamic_states_create_info =
         dynamic_states(&[DynamicState::VIEWPORT, DynamicState::SCISSOR]);
-    let shader_module = load_shader_module("shaders/shader.spv", device.clone()).unwrap();
+    let shader_module = shader_registry.load_module("gradient", device.clone())?;
     let shader_stage_create_info = vec![PipelineShaderStageCreateInfo::default().name(c"main").module(shader_module).stage(ShaderStageFlags::ALL)];
     let vertex_input_state = PipelineVertexInputStateCreateInfo::default();
     let input_assembly_state = PipelineInputAssemblyStateCreateInfo::default()
@@ -53,7 +61,7 @@ pub fn create_graphics_pipeline(
     Ok(unsafe {
         device
             .create_graphics_pipelines(
-                PipelineCache::default(),
+                pipeline_cache,
                 &[graphics_pipeline_create_info],
                 None,
             )
@@ -61,6 +69,234 @@ pub fn create_graphics_pipeline(
     })
 }
 
+pub fn create_egui_pipeline(
+    device: Arc<Device>,
+    render_pass: &RenderPass,
+    descriptor_set_layout: DescriptorSetLayout,
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
+) -> Result<(Owned<PipelineLayout>, Owned<Pipeline>), Error> {
+    let states = [DynamicState::VIEWPORT, DynamicState::SCISSOR];
+    let dynamic_states_create_info = dynamic_states(&states);
+    let vertex_module = Owned::new(
+        device.clone(),
+        shader_registry.load_module("egui.vert", device.clone())?,
+    );
+    let fragment_module = Owned::new(
+        device.clone(),
+        shader_registry.load_module("egui.frag", device.clone())?,
+    );
+    let shader_stage_create_info = vec![
+        PipelineShaderStageCreateInfo::default()
+            .name(c"main")
+            .module(*vertex_module)
+            .stage(ShaderStageFlags::VERTEX),
+        PipelineShaderStageCreateInfo::default()
+            .name(c"main")
+            .module(*fragment_module)
+            .stage(ShaderStageFlags::FRAGMENT),
+    ];
+
+    // egui::epaint::Vertex is pos: [f32; 2], uv: [f32; 2], color: sRGBA u8
+    let vertex_binding_descriptions = [VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(size_of::<egui::epaint::Vertex>() as u32)
+        .input_rate(VertexInputRate::VERTEX)];
+    let vertex_attribute_descriptions = [
+        VertexInputAttributeDescription::default()
+            .location(0)
+            .binding(0)
+            .format(Format::R32G32_SFLOAT)
+            .offset(0),
+        VertexInputAttributeDescription::default()
+            .location(1)
+            .binding(0)
+            .format(Format::R32G32_SFLOAT)
+            .offset(8),
+        VertexInputAttributeDescription::default()
+            .location(2)
+            .binding(0)
+            .format(Format::R8G8B8A8_UNORM)
+            .offset(16),
+    ];
+    let vertex_input_state = PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(&vertex_binding_descriptions)
+        .vertex_attribute_descriptions(&vertex_attribute_descriptions);
+    let input_assembly_state = PipelineInputAssemblyStateCreateInfo::default()
+        .topology(PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+    let viewport_state = PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+    let rasterizer_info = create_rasterizer_state().cull_mode(CullModeFlags::NONE);
+    let multisamping_info = create_multisampling_state();
+
+    // egui outputs premultiplied alpha
+    let color_blending_attachments = [PipelineColorBlendAttachmentState::default()
+        .color_write_mask(ColorComponentFlags::RGBA)
+        .blend_enable(true)
+        .src_color_blend_factor(BlendFactor::ONE)
+        .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(BlendOp::ADD)
+        .src_alpha_blend_factor(BlendFactor::ONE_MINUS_DST_ALPHA)
+        .dst_alpha_blend_factor(BlendFactor::ONE)
+        .alpha_blend_op(BlendOp::ADD)];
+    let color_blending_state_info = create_color_blending_state(&color_blending_attachments);
+
+    let set_layouts = [descriptor_set_layout];
+    let push_constant_ranges = [PushConstantRange::default()
+        .stage_flags(ShaderStageFlags::VERTEX)
+        .offset(0)
+        .size(size_of::<[f32; 2]>() as u32)];
+    let pipeline_layout_create_info = PipelineLayoutCreateInfo::default()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(&push_constant_ranges);
+    let pipeline_layout = unsafe {
+        device
+            .create_pipeline_layout(&pipeline_layout_create_info, None)
+            .unwrap()
+    };
+
+    let graphics_pipeline_create_info = GraphicsPipelineCreateInfo::default()
+        .stages(&shader_stage_create_info)
+        .dynamic_state(&dynamic_states_create_info)
+        .input_assembly_state(&input_assembly_state)
+        .vertex_input_state(&vertex_input_state)
+        .viewport_state(&viewport_state)
+        .color_blend_state(&color_blending_state_info)
+        .multisample_state(&multisamping_info)
+        .rasterization_state(&rasterizer_info)
+        .layout(pipeline_layout)
+        .subpass(0)
+        .render_pass(*render_pass)
+        .base_pipeline_index(-1)
+        .base_pipeline_handle(Pipeline::null());
+    let pipeline = unsafe {
+        device
+            .create_graphics_pipelines(pipeline_cache, &[graphics_pipeline_create_info], None)
+            .unwrap()[0]
+    };
+    Ok((
+        Owned::new(device.clone(), pipeline_layout),
+        Owned::new(device, pipeline),
+    ))
+}
+
+// `set_layouts` starts with the camera at CAMERA_SET, the push constants are shared
+// by both stages.
+pub fn create_mesh_pipeline_layout(
+    device: Arc<Device>,
+    set_layouts: &[DescriptorSetLayout],
+    push_constant_size: u32,
+) -> Owned<PipelineLayout> {
+    let push_constant_ranges = [PushConstantRange::default()
+        .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(push_constant_size)];
+    let pipeline_layout_create_info = PipelineLayoutCreateInfo::default()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(&push_constant_ranges);
+    let pipeline_layout = unsafe {
+        device
+            .create_pipeline_layout(&pipeline_layout_create_info, None)
+            .unwrap()
+    };
+    Owned::new(device, pipeline_layout)
+}
+
+// Opaque, depth tested geometry in the `mesh::Vertex` layout, shaded by mesh.frag.
+// `vertex_shader` decides where transforms come from. With `reverse_z` the depth test
+// keeps the larger value, to match `Projection::reverse_z`.
+pub fn create_mesh_pipeline(
+    device: Arc<Device>,
+    render_pass: &RenderPass,
+    pipeline_layout: PipelineLayout,
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
+    vertex_shader: &str,
+    reverse_z: bool,
+) -> Result<Owned<Pipeline>, Error> {
+    let states = [DynamicState::VIEWPORT, DynamicState::SCISSOR];
+    let dynamic_states_create_info = dynamic_states(&states);
+    let vertex_module = Owned::new(
+        device.clone(),
+        shader_registry.load_module(vertex_shader, device.clone())?,
+    );
+    let fragment_module = Owned::new(
+        device.clone(),
+        shader_registry.load_module("mesh.frag", device.clone())?,
+    );
+    let shader_stage_create_info = vec![
+        PipelineShaderStageCreateInfo::default()
+            .name(c"main")
+            .module(*vertex_module)
+            .stage(ShaderStageFlags::VERTEX),
+        PipelineShaderStageCreateInfo::default()
+            .name(c"main")
+            .module(*fragment_module)
+            .stage(ShaderStageFlags::FRAGMENT),
+    ];
+
+    let vertex_binding_descriptions = [VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(size_of::<Vertex>() as u32)
+        .input_rate(VertexInputRate::VERTEX)];
+    let attribute = |location: u32, format: Format, offset: usize| {
+        VertexInputAttributeDescription::default()
+            .location(location)
+            .binding(0)
+            .format(format)
+            .offset(offset as u32)
+    };
+    let vertex_attribute_descriptions = [
+        attribute(0, Format::R32G32B32_SFLOAT, offset_of!(Vertex, position)),
+        attribute(1, Format::R32G32B32_SFLOAT, offset_of!(Vertex, normal)),
+        attribute(2, Format::R32G32_SFLOAT, offset_of!(Vertex, uv)),
+        attribute(3, Format::R32G32B32A32_SFLOAT, offset_of!(Vertex, tangent)),
+    ];
+    let vertex_input_state = PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(&vertex_binding_descriptions)
+        .vertex_attribute_descriptions(&vertex_attribute_descriptions);
+    let input_assembly_state = PipelineInputAssemblyStateCreateInfo::default()
+        .topology(PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+    let viewport_state = PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+    let rasterizer_info = create_rasterizer_state();
+    let multisamping_info = create_multisampling_state();
+    let depth_stencil_state = PipelineDepthStencilStateCreateInfo::default()
+        .depth_test_enable(true)
+        .depth_write_enable(true)
+        .depth_compare_op(depth_compare_op(reverse_z))
+        .min_depth_bounds(0.0)
+        .max_depth_bounds(1.0);
+    let color_blending_attachments = [create_color_blending_attachment_state()];
+    let color_blending_state_info = create_color_blending_state(&color_blending_attachments);
+
+    let graphics_pipeline_create_info = GraphicsPipelineCreateInfo::default()
+        .stages(&shader_stage_create_info)
+        .dynamic_state(&dynamic_states_create_info)
+        .input_assembly_state(&input_assembly_state)
+        .vertex_input_state(&vertex_input_state)
+        .viewport_state(&viewport_state)
+        .color_blend_state(&color_blending_state_info)
+        .multisample_state(&multisamping_info)
+        .rasterization_state(&rasterizer_info)
+        .depth_stencil_state(&depth_stencil_state)
+        .layout(pipeline_layout)
+        .subpass(0)
+        .render_pass(*render_pass)
+        .base_pipeline_index(-1)
+        .base_pipeline_handle(Pipeline::null());
+    let pipeline = unsafe {
+        device
+            .create_graphics_pipelines(pipeline_cache, &[graphics_pipeline_create_info], None)
+            .unwrap()[0]
+    };
+    Ok(Owned::new(device, pipeline))
+}
+
 fn dynamic_states<'a>(states: &'a [DynamicState]) -> PipelineDynamicStateCreateInfo<'a> {
     PipelineDynamicStateCreateInfo::default().dynamic_states(states)
 }
@@ -1,20 +1,18 @@
-use std::{
-    env,
-    ffi::c_void,
-    fmt::Error,
-    ops::Add,
-};
+use std::{env, ffi::CStr, fmt::Error, ops::Add, sync::Arc};
 
 use ash::{
     ext::debug_utils,
     vk::{
-        ApplicationInfo, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessengerEXT, InstanceCreateFlags, InstanceCreateInfo, API_VERSION_1_2, API_VERSION_1_3, EXT_DEBUG_UTILS_NAME
+        ApplicationInfo, DebugUtilsMessengerEXT, InstanceCreateFlags, InstanceCreateInfo, API_VERSION_1_2, API_VERSION_1_3, EXT_DEBUG_UTILS_NAME
     },
     Entry, Instance,
 };
-use log::{debug, error, info, warn};
+use log::{debug, warn};
 use winit::{raw_window_handle::HasDisplayHandle, window::Window};
 
+use crate::engine::validation::ValidationSink;
+
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
 
 pub fn load_vulkan_library() -> Result<Entry, Error> {
     #[cfg(target_os = "macos")]
@@ -23,7 +21,11 @@ pub fn load_vulkan_library() -> Result<Entry, Error> {
     Ok(unsafe { Entry::load_from(entry_path).unwrap() })
 }
 
-pub fn create_instance(entry: &Entry, window: &Window) -> Result<Instance, Error> {
+pub fn create_instance(
+    entry: &Entry,
+    window: &Window,
+    validation_sink: &Arc<ValidationSink>,
+) -> Result<Instance, Error> {
     let engine_name = c"ELPMRUW";
     let application_name = c"WURMPLE";
     let application_info = ApplicationInfo::default()
@@ -54,54 +56,57 @@ pub fn create_instance(entry: &Entry, window: &Window) -> Result<Instance, Error
         "Loaded {} instance extension properties: {extension_properties:#?}",
         extension_properties.len()
     );
-    let validation_layers = unsafe {
-        entry
-            .enumerate_instance_layer_properties()
-            .unwrap()
-            .iter()
-            .map(|layer| {
-                layer
-                    .layer_name_as_c_str()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string()
-            })
-            .collect::<Vec<String>>()
-    };
-
-    let enabled_layer_support = check_validation_layers(validation_layers);
+    let enabled_layer_support = debug_utils_enabled(entry);
     if enabled_layer_support {
         required_extensions.push(EXT_DEBUG_UTILS_NAME.as_ptr());
     }
+    let enable_layer = validation_sink.config().enable_layer;
+    if enable_layer && !enabled_layer_support {
+        warn!("Validation was requested but {VALIDATION_LAYER:?} is not installed");
+    }
+    let enabled_layers = if enable_layer && enabled_layer_support {
+        vec![VALIDATION_LAYER.as_ptr()]
+    } else {
+        Vec::new()
+    };
     let mut instance_create_info = InstanceCreateInfo::default()
         .application_info(&application_info)
         .flags(InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR)
-        .enabled_extension_names(&required_extensions);
+        .enabled_extension_names(&required_extensions)
+        .enabled_layer_names(&enabled_layers);
 
-    let mut debug_create_info = DebugUtilsMessengerCreateInfoEXT::default()
-        .message_severity(
-            DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | DebugUtilsMessageSeverityFlagsEXT::INFO
-                | DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | DebugUtilsMessageSeverityFlagsEXT::ERROR,
-        )
-        .message_type(
-            DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-        )
-        .pfn_user_callback(Some(debug_callback));
+    let mut debug_create_info = validation_sink.messenger_create_info();
 
     if enabled_layer_support {
         instance_create_info = instance_create_info.push_next(&mut debug_create_info);
     }
 
     let instance = unsafe { entry.create_instance(&instance_create_info, None).unwrap() };
+    validation_sink.set_layer_enabled(!enabled_layers.is_empty());
 
     Ok(instance)
 }
 
+// VK_EXT_debug_utils is only enabled on the instance when the validation layer is present.
+pub fn debug_utils_enabled(entry: &Entry) -> bool {
+    let validation_layers = unsafe {
+        entry
+            .enumerate_instance_layer_properties()
+            .unwrap()
+            .iter()
+            .map(|layer| {
+                layer
+                    .layer_name_as_c_str()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+    };
+    check_validation_layers(validation_layers)
+}
+
 fn check_validation_layers(validation_layers: Vec<String>) -> bool {
     let validation_layer_tbc = vec![String::from("VK_LAYER_KHRONOS_validation")];
     let mut count = 0;
@@ -118,20 +123,9 @@ fn check_validation_layers(validation_layers: Vec<String>) -> bool {
 pub fn create_debugger(
     entry: &Entry,
     instance: &Instance,
+    validation_sink: &Arc<ValidationSink>,
 ) -> (debug_utils::Instance, DebugUtilsMessengerEXT) {
-    let mut debug_create_info = DebugUtilsMessengerCreateInfoEXT::default()
-        .message_severity(
-            DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | DebugUtilsMessageSeverityFlagsEXT::INFO
-                | DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | DebugUtilsMessageSeverityFlagsEXT::ERROR,
-        )
-        .message_type(
-            DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-        )
-        .pfn_user_callback(Some(debug_callback));
+    let debug_create_info = validation_sink.messenger_create_info();
     let debug_instance = debug_utils::Instance::new(entry, instance);
     let debugger = unsafe {
         debug_instance
@@ -140,39 +134,3 @@ pub fn create_debugger(
     };
     (debug_instance, debugger)
 }
-
-unsafe extern "system" fn debug_callback(
-    message_severity: DebugUtilsMessageSeverityFlagsEXT,
-    message_type: DebugUtilsMessageTypeFlagsEXT,
-    callback_data: *const DebugUtilsMessengerCallbackDataEXT<'_>,
-    user_data: *mut c_void,
-) -> u32 {
-    unsafe {
-        let p_callback_data = *callback_data;
-        let message_id_name = p_callback_data
-            .message_id_name_as_c_str()
-            .unwrap()
-            .to_string_lossy();
-        let message_id_number = p_callback_data.message_id_number;
-        let message = p_callback_data
-            .message_as_c_str()
-            .unwrap()
-            .to_string_lossy();
-
-        match message_severity {
-            DebugUtilsMessageSeverityFlagsEXT::WARNING => {
-                warn!("{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n");
-            }
-            DebugUtilsMessageSeverityFlagsEXT::ERROR => {
-                error!("{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n")
-            }
-            _ => {
-                info!("{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n");
-            }
-            _ => {
-                info!("{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n");
-            }
-        }
-    }
-    0
-}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use ash::vk::{
-    DescriptorSet, DescriptorSetLayout, Extent2D, Format, Image, ImageView, Pipeline, PipelineLayout, RenderPass
+    DescriptorSet, DescriptorSetLayout, Extent2D, Format, Image, ImageView, Pipeline, PipelineCache, PushConstantRange, RenderPass
 };
 use ash::{
     ext::debug_utils,
@@ -18,14 +18,17 @@ use swapchain_support_details::SwapchainSupportDetails;
 use winit::window::Window;
 
 use super::allocated_image::AllocatedImage;
-use super::deletion_queue::DeletionQueue;
+use super::handles::Owned;
+use super::validation::ValidationSink;
 
 mod compute_pipeline;
 mod descriptor;
 mod device;
 mod graphics_pipeline;
 mod instance;
+mod pipeline_cache;
 mod renderpass;
+mod shader_registry;
 mod swapchain;
 mod egui_configuration;
 mod swapchain_support_details;
@@ -34,6 +37,15 @@ mod util;
 pub type SwapchainSupportDetail = SwapchainSupportDetails;
 pub type DescriptorAllocato = DescriptorAllocator;
 pub type EGUIConfig = EGUIConfiguration;
+pub use compute_pipeline::{
+    create_compute_pipeline, create_compute_pipeline_layout, ComputeEffect, ComputePushConstants,
+};
+pub use descriptor::{write_storage_buffer, write_uniform_buffer, DescriptorLayoutBuilder, PoolSizeRatio};
+pub use device::device_extensions;
+pub use graphics_pipeline::{create_egui_pipeline, create_mesh_pipeline, create_mesh_pipeline_layout};
+pub use instance::debug_utils_enabled;
+pub use pipeline_cache::{default_cache_path, PersistentPipelineCache};
+pub use shader_registry::{ShaderRegistry, SHADER_DIR_ENV};
 
 #[derive(Default, Clone, Copy)]
 pub struct QueueFamilyIndices {
@@ -77,17 +89,21 @@ impl QueueFamilyIndices {
     }
 }
 
-pub fn create_entry_and_instance(window: &Window) -> (Entry, Instance) {
+pub fn create_entry_and_instance(
+    window: &Window,
+    validation_sink: &Arc<ValidationSink>,
+) -> (Entry, Instance) {
     let entry = load_vulkan_library().unwrap();
-    let instance = create_instance(&entry, window).unwrap();
+    let instance = create_instance(&entry, window, validation_sink).unwrap();
     (entry, instance)
 }
 
 pub fn create_debugger(
     entry: &Entry,
     instance: &Instance,
+    validation_sink: &Arc<ValidationSink>,
 ) -> (debug_utils::Instance, DebugUtilsMessengerEXT) {
-    instance::create_debugger(&entry, &instance)
+    instance::create_debugger(&entry, &instance, validation_sink)
 }
 pub fn create_device(
     instance: &Instance,
@@ -149,7 +165,7 @@ pub fn get_swapchain_support_details(
 }
 
 pub fn create_allocated_image(
-    device: &Device,
+    device: &Arc<Device>,
     swapchain_device: &ash::khr::swapchain::Device,
     swapchain_support_details: &SwapchainSupportDetails,
     swapchain: SwapchainKHR,
@@ -170,11 +186,11 @@ pub fn create_allocated_image(
 }
 
 pub fn create_image_views(
-    device: &Device,
+    device: &Arc<Device>,
     swapchain_device: &ash::khr::swapchain::Device,
     swapchain_support_details: SwapchainSupportDetails,
     swapchain: SwapchainKHR,
-) -> (Vec<Image>, Vec<ImageView>) {
+) -> (Vec<Image>, Vec<Owned<ImageView>>) {
     create_swapchain_image_and_views(
         device,
         swapchain_device,
@@ -188,28 +204,53 @@ pub fn create_graphics_pipelines(
     device: Arc<Device>,
     render_pass: &RenderPass,
     extent: &Extent2D,
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
 ) -> Vec<Pipeline> {
-    graphics_pipeline::create_graphics_pipeline(device, render_pass, extent).unwrap()
+    graphics_pipeline::create_graphics_pipeline(
+        device,
+        render_pass,
+        extent,
+        shader_registry,
+        pipeline_cache,
+    )
+    .unwrap()
+}
+
+pub fn create_mesh_render_pass(device: &Device, color_format: Format, depth_format: Format) -> RenderPass {
+    renderpass::allocate_mesh_render_pass(device, color_format, depth_format).unwrap()
 }
 
-pub fn create_render_pass(device: &Device, format: &Format) -> RenderPass {
-    renderpass::allocate_render_pass(&device, format).unwrap()
+pub fn create_pipeline_cache(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    device: &Arc<Device>,
+) -> PersistentPipelineCache {
+    PersistentPipelineCache::new(instance, physical_device, device, default_cache_path()).unwrap()
 }
 
 pub fn init_descriptors(
     device: Arc<Device>,
     allocated_image: Arc<AllocatedImage>,
-    deletion_queue: &mut DeletionQueue,
-) -> (Arc<DescriptorAllocator>, DescriptorSetLayout, DescriptorSet) {
-    descriptor::init_descriptors(device, allocated_image, deletion_queue).unwrap()
+) -> (Arc<DescriptorAllocator>, Owned<DescriptorSetLayout>, DescriptorSet) {
+    descriptor::init_descriptors(device, allocated_image).unwrap()
 }
 
 pub fn compute_pipeline(
     device: Arc<Device>,
     layouts: &[DescriptorSetLayout],
-    deletion_queue: &mut DeletionQueue,
-) -> (PipelineLayout, Pipeline) {
-    compute_pipeline::init_background_pipelines(device, layouts, deletion_queue).unwrap()
+    push_constant_ranges: &[PushConstantRange],
+    shader_registry: &ShaderRegistry,
+    pipeline_cache: PipelineCache,
+) -> Vec<ComputeEffect> {
+    compute_pipeline::init_background_pipelines(
+        device,
+        layouts,
+        push_constant_ranges,
+        shader_registry,
+        pipeline_cache,
+    )
+    .unwrap()
 }
 
 
@@ -0,0 +1,294 @@
+use std::{
+    env, fs,
+    io::Error,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ash::{
+    vk::{
+        PhysicalDevice, PhysicalDeviceIDProperties, PhysicalDeviceProperties2, PipelineCache,
+        PipelineCacheCreateInfo, PipelineCacheHeaderVersion, UUID_SIZE,
+    },
+    Device, Instance,
+};
+use log::{debug, warn};
+
+use crate::engine::handles::Owned;
+
+pub const PIPELINE_CACHE_ENV: &str = "WURMPLE_PIPELINE_CACHE";
+const MAGIC: &[u8; 8] = b"WRMPLPC1";
+const HEADER_SIZE: usize = MAGIC.len() + 4 * 3 + UUID_SIZE * 2;
+const VK_HEADER_SIZE: usize = 16 + UUID_SIZE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PipelineCacheHeader {
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub driver_version: u32,
+    pub driver_uuid: [u8; UUID_SIZE],
+    pub pipeline_cache_uuid: [u8; UUID_SIZE],
+}
+
+pub struct PersistentPipelineCache {
+    pub cache: Owned<PipelineCache>,
+    path: PathBuf,
+    header: PipelineCacheHeader,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+pub fn default_cache_path() -> PathBuf {
+    if let Some(path) = env::var_os(PIPELINE_CACHE_ENV) {
+        return PathBuf::from(path);
+    }
+    env::home_dir()
+        .map(|home| home.join(".cache"))
+        .unwrap_or_else(env::temp_dir)
+        .join("wurmple")
+        .join("pipeline_cache.bin")
+}
+
+impl PipelineCacheHeader {
+    pub fn query(instance: &Instance, physical_device: PhysicalDevice) -> Self {
+        let mut id_properties = PhysicalDeviceIDProperties::default();
+        let mut properties = PhysicalDeviceProperties2::default().push_next(&mut id_properties);
+        unsafe { instance.get_physical_device_properties2(physical_device, &mut properties) };
+        let properties = properties.properties;
+        Self {
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            driver_version: properties.driver_version,
+            driver_uuid: id_properties.driver_uuid,
+            pipeline_cache_uuid: properties.pipeline_cache_uuid,
+        }
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.vendor_id.to_le_bytes());
+        bytes.extend_from_slice(&self.device_id.to_le_bytes());
+        bytes.extend_from_slice(&self.driver_version.to_le_bytes());
+        bytes.extend_from_slice(&self.driver_uuid);
+        bytes.extend_from_slice(&self.pipeline_cache_uuid);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+        let uuid_offset = MAGIC.len() + 12;
+        Some(Self {
+            vendor_id: read_u32(bytes, MAGIC.len()),
+            device_id: read_u32(bytes, MAGIC.len() + 4),
+            driver_version: read_u32(bytes, MAGIC.len() + 8),
+            driver_uuid: bytes[uuid_offset..uuid_offset + UUID_SIZE]
+                .try_into()
+                .unwrap(),
+            pipeline_cache_uuid: bytes[uuid_offset + UUID_SIZE..HEADER_SIZE]
+                .try_into()
+                .unwrap(),
+        })
+    }
+
+    // The driver writes its own header in front of the cache data, check it as well
+    // so a blob from another device never reaches vkCreatePipelineCache.
+    pub fn matches_vulkan_header(&self, data: &[u8]) -> bool {
+        data.len() >= VK_HEADER_SIZE
+            && read_u32(data, 0) as usize >= VK_HEADER_SIZE
+            && read_u32(data, 4) == PipelineCacheHeaderVersion::ONE.as_raw() as u32
+            && read_u32(data, 8) == self.vendor_id
+            && read_u32(data, 12) == self.device_id
+            && data[16..VK_HEADER_SIZE] == self.pipeline_cache_uuid
+    }
+}
+
+pub fn load_cache_data(path: &Path, header: &PipelineCacheHeader) -> Option<Vec<u8>> {
+    let bytes = fs::read(path).ok()?;
+    match PipelineCacheHeader::from_bytes(&bytes) {
+        Some(file_header) if file_header == *header => {
+            let data = bytes[HEADER_SIZE..].to_vec();
+            if header.matches_vulkan_header(&data) {
+                Some(data)
+            } else {
+                warn!("Pipeline cache {path:?} has a corrupt vulkan header, ignoring it");
+                None
+            }
+        }
+        _ => {
+            warn!("Pipeline cache {path:?} was written by another device or driver, ignoring it");
+            None
+        }
+    }
+}
+
+impl PersistentPipelineCache {
+    pub fn new(
+        instance: &Instance,
+        physical_device: PhysicalDevice,
+        device: &Arc<Device>,
+        path: PathBuf,
+    ) -> Result<Self, Error> {
+        let header = PipelineCacheHeader::query(instance, physical_device);
+        let initial_data = load_cache_data(&path, &header).unwrap_or_default();
+        debug!(
+            "Creating pipeline cache with {} bytes loaded from {path:?}",
+            initial_data.len()
+        );
+        let create_info = PipelineCacheCreateInfo::default().initial_data(&initial_data);
+        let cache =
+            unsafe { device.create_pipeline_cache(&create_info, None) }.map_err(Error::other)?;
+        Ok(Self {
+            cache: Owned::new(device.clone(), cache),
+            path,
+            header,
+        })
+    }
+
+    pub fn save(&self, device: &Device) -> Result<(), Error> {
+        let data = unsafe { device.get_pipeline_cache_data(*self.cache) }.map_err(Error::other)?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&data);
+        fs::write(&self.path, bytes)?;
+        debug!(
+            "Saved {} bytes of pipeline cache to {:?}",
+            data.len(),
+            self.path
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> PipelineCacheHeader {
+        PipelineCacheHeader {
+            vendor_id: 0x10de,
+            device_id: 0x2684,
+            driver_version: 0x0234_5678,
+            driver_uuid: [1; UUID_SIZE],
+            pipeline_cache_uuid: [2; UUID_SIZE],
+        }
+    }
+
+    // What a driver would put in front of its cache data for `header`.
+    fn vulkan_data(header: &PipelineCacheHeader) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(VK_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&(PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_le_bytes());
+        data.extend_from_slice(&header.vendor_id.to_le_bytes());
+        data.extend_from_slice(&header.device_id.to_le_bytes());
+        data.extend_from_slice(&header.pipeline_cache_uuid);
+        data.extend_from_slice(b"pipelines");
+        data
+    }
+
+    // Writes a cache file as `written` would have saved it and loads it back as `current`.
+    fn load_as(
+        name: &str,
+        written: PipelineCacheHeader,
+        current: PipelineCacheHeader,
+    ) -> Option<Vec<u8>> {
+        let path = env::temp_dir().join(format!(
+            "wurmple_pipeline_cache_{name}_{}.bin",
+            std::process::id()
+        ));
+        let mut bytes = written.to_bytes();
+        bytes.extend_from_slice(&vulkan_data(&written));
+        fs::write(&path, bytes).unwrap();
+        let data = load_cache_data(&path, &current);
+        fs::remove_file(&path).unwrap();
+        data
+    }
+
+    #[test]
+    fn header_round_trips() {
+        let bytes = header().to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE);
+        assert_eq!(PipelineCacheHeader::from_bytes(&bytes), Some(header()));
+    }
+
+    #[test]
+    fn header_rejects_bad_magic_and_short_input() {
+        let mut bytes = header().to_bytes();
+        assert_eq!(
+            PipelineCacheHeader::from_bytes(&bytes[..HEADER_SIZE - 1]),
+            None
+        );
+        bytes[0] = b'X';
+        assert_eq!(PipelineCacheHeader::from_bytes(&bytes), None);
+    }
+
+    #[test]
+    fn loads_data_from_the_same_device() {
+        assert_eq!(
+            load_as("same", header(), header()),
+            Some(vulkan_data(&header()))
+        );
+    }
+
+    #[test]
+    fn rejects_data_from_another_device_or_driver() {
+        let mismatches = [
+            (
+                "vendor",
+                PipelineCacheHeader {
+                    vendor_id: 0x1002,
+                    ..header()
+                },
+            ),
+            (
+                "device",
+                PipelineCacheHeader {
+                    device_id: 0x73bf,
+                    ..header()
+                },
+            ),
+            (
+                "driver",
+                PipelineCacheHeader {
+                    driver_version: 0x0234_5679,
+                    ..header()
+                },
+            ),
+            (
+                "driver_uuid",
+                PipelineCacheHeader {
+                    driver_uuid: [3; UUID_SIZE],
+                    ..header()
+                },
+            ),
+            (
+                "cache_uuid",
+                PipelineCacheHeader {
+                    pipeline_cache_uuid: [3; UUID_SIZE],
+                    ..header()
+                },
+            ),
+        ];
+        for (name, written) in mismatches {
+            assert_eq!(
+                load_as(name, written, header()),
+                None,
+                "{name} mismatch was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_corrupt_vulkan_header() {
+        let mut data = vulkan_data(&header());
+        data[8] ^= 0xff;
+        assert!(!header().matches_vulkan_header(&data));
+        assert!(!header().matches_vulkan_header(&vulkan_data(&header())[..VK_HEADER_SIZE - 1]));
+    }
+}
@@ -2,16 +2,89 @@ use std::io::Error;
 
 use ash::{
     vk::{
-        AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, Format,
-        ImageLayout, PipelineBindPoint, RenderPass, RenderPassCreateInfo, SampleCountFlags,
-        SubpassDescription,
+        AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
+        AttachmentStoreOp, Format, ImageLayout, PipelineBindPoint, PipelineStageFlags, RenderPass,
+        RenderPassCreateInfo, SampleCountFlags, SubpassDependency, SubpassDescription,
+        SUBPASS_EXTERNAL,
     },
     Device,
 };
 
 
-pub fn allocate_render_pass(device: &Device, format: &Format) -> Result<RenderPass, Error> {
-    let color_attachment = create_attachment(*format);
+// For passes that draw on top of what is already in the image, e.g. UI over the background
+pub fn allocate_overlay_render_pass(device: &Device, format: &Format) -> Result<RenderPass, Error> {
+    allocate_render_pass_with_layouts(
+        device,
+        format,
+        AttachmentLoadOp::LOAD,
+        ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    )
+}
+
+// Draws geometry over the background with a depth buffer that is cleared every pass. The
+// color image stays in GENERAL around the pass, as the compute background leaves it.
+pub fn allocate_mesh_render_pass(
+    device: &Device,
+    color_format: Format,
+    depth_format: Format,
+) -> Result<RenderPass, Error> {
+    let attachments = [
+        create_attachment(
+            color_format,
+            AttachmentLoadOp::LOAD,
+            ImageLayout::GENERAL,
+            ImageLayout::GENERAL,
+        ),
+        create_attachment(
+            depth_format,
+            AttachmentLoadOp::CLEAR,
+            ImageLayout::UNDEFINED,
+            ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        )
+        .store_op(AttachmentStoreOp::DONT_CARE),
+    ];
+    let color_attachment_ref = vec![create_attachment_ref()];
+    let depth_attachment_ref = AttachmentReference::default()
+        .attachment(1)
+        .layout(ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+    let subpass_description = create_subpass_description(&color_attachment_ref)
+        .depth_stencil_attachment(&depth_attachment_ref);
+    // Wait for the compute background and for the previous frame's depth writes
+    let dependencies = [SubpassDependency::default()
+        .src_subpass(SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_stage_mask(
+            PipelineStageFlags::COMPUTE_SHADER | PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        )
+        .src_access_mask(AccessFlags::SHADER_WRITE | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        .dst_stage_mask(
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+        )
+        .dst_access_mask(
+            AccessFlags::COLOR_ATTACHMENT_READ
+                | AccessFlags::COLOR_ATTACHMENT_WRITE
+                | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )];
+    Ok(unsafe {
+        device
+            .create_render_pass(
+                &render_pass_create_info(&attachments, &[subpass_description])
+                    .dependencies(&dependencies),
+                None,
+            )
+            .unwrap()
+    })
+}
+
+fn allocate_render_pass_with_layouts(
+    device: &Device,
+    format: &Format,
+    load_op: AttachmentLoadOp,
+    initial_layout: ImageLayout,
+    final_layout: ImageLayout,
+) -> Result<RenderPass, Error> {
+    let color_attachment = create_attachment(*format, load_op, initial_layout, final_layout);
     let color_attachment_ref = vec![create_attachment_ref()];
     let subpass_description = create_subpass_description(&color_attachment_ref);
     Ok(unsafe {
@@ -33,16 +106,21 @@ fn render_pass_create_info<'a>(
         .subpasses(subpass_description)
 }
 
-fn create_attachment(image_format: Format) -> AttachmentDescription {
+fn create_attachment(
+    image_format: Format,
+    load_op: AttachmentLoadOp,
+    initial_layout: ImageLayout,
+    final_layout: ImageLayout,
+) -> AttachmentDescription {
     AttachmentDescription::default()
         .format(image_format)
         .samples(SampleCountFlags::TYPE_1)
-        .load_op(AttachmentLoadOp::CLEAR)
+        .load_op(load_op)
         .store_op(AttachmentStoreOp::STORE)
         .stencil_load_op(AttachmentLoadOp::DONT_CARE)
         .stencil_store_op(AttachmentStoreOp::DONT_CARE)
-        .initial_layout(ImageLayout::UNDEFINED)
-        .final_layout(ImageLayout::PRESENT_SRC_KHR)
+        .initial_layout(initial_layout)
+        .final_layout(final_layout)
 }
 
 fn create_attachment_ref() -> AttachmentReference {
@@ -0,0 +1,86 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    env,
+    io::{Error, ErrorKind},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use ash::{vk::ShaderModule, Device};
+use log::debug;
+
+use super::util::create_shader_module;
+
+pub const SHADER_DIR_ENV: &str = "WURMPLE_SHADER_DIR";
+
+// Compiled from shaders/ by build.rs
+const EMBEDDED_SHADERS: &[(&str, &[u8])] = &[
+    ("gradient", include_bytes!(concat!(env!("OUT_DIR"), "/gradient.spv"))),
+    ("sky", include_bytes!(concat!(env!("OUT_DIR"), "/sky.spv"))),
+    ("noise", include_bytes!(concat!(env!("OUT_DIR"), "/noise.spv"))),
+    ("cull", include_bytes!(concat!(env!("OUT_DIR"), "/cull.spv"))),
+    ("egui.vert", include_bytes!(concat!(env!("OUT_DIR"), "/egui.vert.spv"))),
+    ("egui.frag", include_bytes!(concat!(env!("OUT_DIR"), "/egui.frag.spv"))),
+    ("mesh.vert", include_bytes!(concat!(env!("OUT_DIR"), "/mesh.vert.spv"))),
+    ("mesh.frag", include_bytes!(concat!(env!("OUT_DIR"), "/mesh.frag.spv"))),
+    ("mesh_instanced.vert", include_bytes!(concat!(env!("OUT_DIR"), "/mesh_instanced.vert.spv"))),
+];
+
+pub struct ShaderRegistry {
+    shaders: HashMap<String, Cow<'static, [u8]>>,
+    override_dir: Option<PathBuf>,
+}
+
+impl ShaderRegistry {
+    pub fn new() -> Self {
+        let shaders = EMBEDDED_SHADERS
+            .iter()
+            .map(|(name, code)| (name.to_string(), Cow::Borrowed(*code)))
+            .collect();
+        Self {
+            shaders,
+            override_dir: env::var_os(SHADER_DIR_ENV).map(PathBuf::from),
+        }
+    }
+
+    pub fn with_override_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.override_dir = Some(dir.into());
+        self
+    }
+
+    pub fn register<T: Into<Cow<'static, [u8]>>>(&mut self, name: &str, code: T) {
+        self.shaders.insert(name.to_string(), code.into());
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.shaders.keys().map(|name| name.as_str()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Cow<'_, [u8]>, Error> {
+        if let Some(dir) = &self.override_dir {
+            let path = dir.join(format!("{name}.spv"));
+            if path.is_file() {
+                debug!("Loading shader {name} from {path:?}");
+                return Ok(Cow::Owned(std::fs::read(path)?));
+            }
+        }
+        self.shaders
+            .get(name)
+            .map(|code| Cow::Borrowed(code.as_ref()))
+            .ok_or(Error::new(
+                ErrorKind::NotFound,
+                format!("No shader named {name} in registry"),
+            ))
+    }
+
+    pub fn load_module(&self, name: &str, device: Arc<Device>) -> Result<ShaderModule, Error> {
+        create_shader_module(&self.get(name)?, device)
+    }
+}
+
+impl Default for ShaderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
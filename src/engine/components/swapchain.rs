@@ -11,7 +11,8 @@ use vk_mem::{Alloc, MemoryUsage};
 use winit::window::{self, Window};
 
 use crate::engine::{
-    allocated_image::AllocatedImage, egui_renderer::RenderInformation, image_ops::{image_create_info, image_subresource_range, image_view_create_info}
+    allocated_image::AllocatedImage, egui_renderer::RenderInformation, handles::Owned, image_ops::{image_create_info, image_subresource_range, image_view_create_info},
+    memory_stats::{track_allocation, AllocationCategory},
 };
 
 use super::{
@@ -76,7 +77,7 @@ pub fn create_swapchain(
 }
 
 pub fn create_allocated_image(
-    device: &Device,
+    device: &Arc<Device>,
     swapchain_device: &ash::khr::swapchain::Device,
     swapchain_support_details: &SwapchainSupportDetails,
     swapchain: SwapchainKHR,
@@ -105,6 +106,7 @@ pub fn create_allocated_image(
             .create_image(&image_create_info, &allocation_create_info)
             .unwrap()
     };
+    track_allocation(&vma_allocator, &allocation, AllocationCategory::Image);
 
     let image_view_create_info =
         image_view_create_info(image, Format::R16G16B16A16_SFLOAT, ImageAspectFlags::COLOR);
@@ -114,6 +116,8 @@ pub fn create_allocated_image(
             .unwrap()
     };
     let allocated_image = AllocatedImage::new(
+        device.clone(),
+        vma_allocator,
         image,
         image_view,
         allocation,
@@ -124,11 +128,11 @@ pub fn create_allocated_image(
 }
 
 pub fn create_swapchain_image_and_views(
-    device: &Device,
+    device: &Arc<Device>,
     swapchain_device: &ash::khr::swapchain::Device,
     swapchain_support_details: SwapchainSupportDetails,
     swapchain: SwapchainKHR,
-) -> Result<(Vec<Image>, Vec<ImageView>), Error> {
+) -> Result<(Vec<Image>, Vec<Owned<ImageView>>), Error> {
     unsafe {
         let images = swapchain_device.get_swapchain_images(swapchain).unwrap();
         let image_views = images
@@ -152,11 +156,12 @@ pub fn create_swapchain_image_and_views(
                             .a(ComponentSwizzle::IDENTITY),
                     );
 
-                device
+                let image_view = device
                     .create_image_view(&image_view_create_info, None)
-                    .unwrap()
+                    .unwrap();
+                Owned::new(device.clone(), image_view)
             })
-            .collect::<Vec<ImageView>>();
+            .collect::<Vec<Owned<ImageView>>>();
         Ok((images, image_views))
     }
 }
@@ -1,13 +1,9 @@
-use std::{io::{Cursor, Error}, path::Path, sync::Arc};
+use std::{io::{Cursor, Error}, sync::Arc};
 
 use ash::{util::read_spv, vk::{ShaderModule, ShaderModuleCreateInfo}, Device};
 
-pub fn load_shader_module(file_path: &str, device: Arc<Device>) -> Result<ShaderModule, Error> {
-    let code = read_spv(&mut read_file_as_cursor(file_path)).unwrap();
+pub fn create_shader_module(code: &[u8], device: Arc<Device>) -> Result<ShaderModule, Error> {
+    let code = read_spv(&mut Cursor::new(code))?;
     let create_info = ShaderModuleCreateInfo::default().code(&code);
     Ok(unsafe { device.create_shader_module(&create_info, None).unwrap() })
 }
-
-pub fn read_file_as_cursor<P: AsRef<Path>>(path: P) -> Cursor<Vec<u8>> {
-     Cursor::new(std::fs::read(path).unwrap())
-}
@@ -1,14 +1,13 @@
 use std::sync::{Arc};
 
 use ash::{
-    ext::debug_utils,
     khr::{surface, swapchain},
     vk::{
-        DebugUtilsMessengerEXT, DescriptorSet, DescriptorSetLayout, Extent2D, Image, ImageView, PhysicalDevice, Pipeline, PipelineLayout, Queue, SurfaceKHR, SwapchainKHR
+        DescriptorSet, DescriptorSetLayout, Extent2D, Image, ImageView, PhysicalDevice, Queue, SurfaceKHR
     },
     Device, Entry, Instance,
 };
-use log::debug;
+use log::{debug, warn};
 use vk_mem::Allocator;
 use winit::{
     raw_window_handle::{HasDisplayHandle, HasWindowHandle},
@@ -18,79 +17,146 @@ use winit::{
 use super::{
     allocated_image::AllocatedImage,
     components::{
-        compute_pipeline, create_allocated_image, create_debugger, create_device, create_entry_and_instance, create_image_views, create_swapchain, get_queue_family_indices, get_swapchain_support_details, init_descriptors, DescriptorAllocato, QueueFamilyIndices, SwapchainSupportDetail
+        compute_pipeline, create_allocated_image, create_pipeline_cache, PersistentPipelineCache, ComputeEffect, ComputePushConstants, create_debugger, create_device, debug_utils_enabled, create_entry_and_instance, create_image_views, create_swapchain, get_queue_family_indices, get_swapchain_support_details, init_descriptors, DescriptorAllocato, QueueFamilyIndices, ShaderRegistry, SwapchainSupportDetail
     },
-    deletion_queue::DeletionQueue
+    debug_utils::DebugUtils,
+    handles::{DeviceContext, Owned, Surface, Swapchain},
+    validation::{ValidationConfig, ValidationSink},
 };
 
 pub const MAX_FRAMES: u32 = 2;
-#[allow(dead_code)]
-pub struct VkConfiguration {
-    entry: Entry,
-    pub instance: Instance,
-    pub surface: Arc<SurfaceKHR>,
-    pub debug_instance: debug_utils::Instance,
-    pub debugger: DebugUtilsMessengerEXT,
-    pub physical_device: PhysicalDevice,
-    pub device: Arc<Device>,
-    pub indices: QueueFamilyIndices,
-    pub graphics_queue: Arc<Queue>,
-    pub surface_instance: surface::Instance,
-    pub swapchain_device: swapchain::Device,
+// Everything tied to the window surface. It goes away while the app is suspended and
+// is rebuilt on resume, fields are in teardown order.
+pub struct Presentation {
+    pub images: Vec<Image>,
+    pub image_views: Vec<Owned<ImageView>>,
+    pub swapchain: Swapchain,
     pub swapchain_support_details: SwapchainSupportDetail,
     pub extent: Extent2D,
-    pub swapchain: SwapchainKHR,
+    pub surface: Surface,
+}
+
+fn create_surface(entry: &Entry, instance: &Instance, window: &Window) -> SurfaceKHR {
+    unsafe {
+        ash_window::create_surface(
+            entry,
+            instance,
+            window.display_handle().unwrap().as_raw(),
+            window.window_handle().unwrap().as_raw(),
+            None,
+        )
+    }
+    .unwrap()
+}
+
+impl Presentation {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        window: &Window,
+        surface: Surface,
+        physical_device: PhysicalDevice,
+        device: &Arc<Device>,
+        surface_instance: &surface::Instance,
+        swapchain_device: &swapchain::Device,
+        indices: QueueFamilyIndices,
+        debug_utils: &DebugUtils,
+    ) -> Self {
+        let swapchain_support_details =
+            get_swapchain_support_details(physical_device, surface_instance, *surface, window);
+        let extent = swapchain_support_details
+            .clone()
+            .choose_swapchain_extent(window);
+        let swapchain = create_swapchain(
+            physical_device,
+            swapchain_device,
+            surface_instance,
+            *surface,
+            window,
+            indices,
+        );
+        let swapchain = Swapchain::new(swapchain_device.clone(), swapchain);
+        let (images, image_views) = create_image_views(
+            device,
+            swapchain_device,
+            swapchain_support_details.clone(),
+            *swapchain,
+        );
+        debug_utils.name(*surface, "surface");
+        debug_utils.name(*swapchain, "swapchain");
+        for (idx, (image, image_view)) in images.iter().zip(&image_views).enumerate() {
+            debug_utils.name(*image, &format!("swapchain image {idx}"));
+            debug_utils.name(**image_view, &format!("swapchain image view {idx}"));
+        }
+        Self {
+            images,
+            image_views,
+            swapchain,
+            swapchain_support_details,
+            extent,
+            surface,
+        }
+    }
+}
+
+// Fields are dropped in declaration order: pipelines before their layouts, the
+// presentation before the allocator, everything before `context`.
+#[allow(dead_code)]
+pub struct VkConfiguration {
+    pub background_effects: Vec<ComputeEffect>,
+    pub descriptor_set: DescriptorSet,
+    pub descriptor_set_layout: Owned<DescriptorSetLayout>,
+    pub descriptor_allocator: Arc<DescriptorAllocato>,
+    pub pipeline_cache: PersistentPipelineCache,
+    pub shader_registry: ShaderRegistry,
     pub allocated_image: Arc<AllocatedImage>,
-    pub images: Vec<Image>,
-    pub image_views: Vec<ImageView>,
-    pub main_deletion_queue: DeletionQueue,
+    pub presentation: Option<Presentation>,
     pub vma_allocator: Arc<Allocator>,
-    pub descriptor_allocator: Arc<DescriptorAllocato>,
-    pub descriptor_set_layout: DescriptorSetLayout,
-    pub descriptor_set: DescriptorSet,
-    pub compute_pipeline: Pipeline,
-    pub compute_pipeline_layout: PipelineLayout,
+    pub swapchain_device: swapchain::Device,
+    pub graphics_queue: Arc<Queue>,
+    pub indices: QueueFamilyIndices,
+    pub debug_utils: DebugUtils,
+    pub validation_sink: Arc<ValidationSink>,
+    pub surface_instance: surface::Instance,
+    pub physical_device: PhysicalDevice,
+    pub device: Arc<Device>,
+    pub instance: Instance,
+    context: DeviceContext,
+    // The loader has to outlive every call through the instance
+    entry: Entry,
     //graphics_pipelines: Vec<Pipeline>, render_pass: RenderPass,
 }
 
 #[allow(dead_code)]
 impl VkConfiguration {
-    pub fn new(window: &Window) -> Self {
-        let mut main_deletion_queue: DeletionQueue = DeletionQueue::new();
-        let (entry, instance) = create_entry_and_instance(window);
-        let (debug_instance, debugger) = create_debugger(&entry, &instance);
-        let surface = unsafe {
-            ash_window::create_surface(
-                &entry,
-                &instance,
-                window.display_handle().unwrap().as_raw(),
-                window.window_handle().unwrap().as_raw(),
-                None,
-            )
-        }
-        .unwrap();
+    pub fn new(
+        window: &Window,
+        validation: ValidationConfig,
+        shader_registry: ShaderRegistry,
+    ) -> Self {
+        let validation_sink = ValidationSink::new(validation);
+        let (entry, instance) = create_entry_and_instance(window, &validation_sink);
+        let (debug_instance, debugger) = create_debugger(&entry, &instance, &validation_sink);
+        let surface = create_surface(&entry, &instance, window);
         let surface_instance = ash::khr::surface::Instance::new(&entry, &instance);
         let (physical_device, device) =
             create_device(&instance, &surface_instance, surface, window);
         let device_arc = Arc::new(device);
+        let debug_utils = DebugUtils::new(&instance, &device_arc, debug_utils_enabled(&entry));
         let indices =
             get_queue_family_indices(physical_device, &instance, &surface_instance, surface);
         let graphics_queue =
             unsafe { device_arc.get_device_queue(indices.graphics_q_idx.unwrap(), 0) };
         let swapchain_device: ash::khr::swapchain::Device =
             swapchain::Device::new(&instance, &device_arc);
-        let swapchain_support_details =
-            get_swapchain_support_details(physical_device, &surface_instance, surface, window);
-        let extent = swapchain_support_details
-            .clone()
-            .choose_swapchain_extent(window);
-        let swapchain = create_swapchain(
+        let presentation = Presentation::new(
+            window,
+            Surface::new(surface_instance.clone(), surface),
             physical_device,
-            &swapchain_device,
+            &device_arc,
             &surface_instance,
-            surface,
-            window,
+            &swapchain_device,
             indices,
+            &debug_utils,
         );
         let vma_allocator_create_info =
             vk_mem::AllocatorCreateInfo::new(&instance, &device_arc, physical_device);
@@ -99,27 +165,14 @@ impl VkConfiguration {
         let allocated_image = Arc::new(create_allocated_image(
             &device_arc,
             &swapchain_device,
-            &swapchain_support_details,
-            swapchain,
+            &presentation.swapchain_support_details,
+            *presentation.swapchain,
             vma_allocator.clone(),
         ));
 
-        let (images, image_views) = create_image_views(
-            &device_arc,
-            &swapchain_device,
-            swapchain_support_details.clone(),
-            swapchain,
-        );
-
-        Self::populate_queue(
-            &mut main_deletion_queue,
-            device_arc.clone(),
-            allocated_image.clone(),
-        );
-
         debug!("SHIT AINT MAKIN SENSE");
         let (descriptor_allocator, descriptor_set_layout, descriptor_set) =
-            init_descriptors(device_arc.clone(), allocated_image.clone(), &mut main_deletion_queue);
+            init_descriptors(device_arc.clone(), allocated_image.clone());
         /*    let render_pass = create_render_pass(
                  &device,
                  &swapchain_support_details.choose_swapchain_format().format,
@@ -127,62 +180,115 @@ impl VkConfiguration {
             let graphics_pipelines = create_graphics_pipelines(&device, &render_pass, &extent);
         */
         debug!("SHIT AINT MAKIN SENSE2");
-        let (compute_pipeline_layout, compute_pipeline) = compute_pipeline(device_arc.clone(), &[descriptor_set_layout], &mut main_deletion_queue);
+        let pipeline_cache = create_pipeline_cache(&instance, physical_device, &device_arc);
+        let background_effects = compute_pipeline(
+            device_arc.clone(),
+            &[*descriptor_set_layout],
+            &[ComputePushConstants::push_constant_range()],
+            &shader_registry,
+            *pipeline_cache.cache,
+        );
 
-        Self {
-            entry,
-            instance,
-            surface: Arc::new(surface),
+        let context = DeviceContext {
+            instance: instance.clone(),
+            device: device_arc.clone(),
             debug_instance,
             debugger,
-            physical_device,
-            device: device_arc.clone(),
-            indices,
-            graphics_queue: Arc::new(graphics_queue),
-            surface_instance,
-            swapchain_device,
-            swapchain_support_details,
-            extent,
-            swapchain,
+            validation_sink: validation_sink.clone(),
+        };
+
+        let configuration = Self {
+            background_effects,
+            descriptor_set,
+            descriptor_set_layout,
+            descriptor_allocator,
+            pipeline_cache,
+            shader_registry,
             allocated_image,
-            main_deletion_queue,
+            presentation: Some(presentation),
             vma_allocator,
-            images,
-            image_views,
-            descriptor_allocator,
-            descriptor_set_layout,
-            descriptor_set,
-            compute_pipeline,
-            compute_pipeline_layout
+            swapchain_device,
+            graphics_queue: Arc::new(graphics_queue),
+            indices,
+            debug_utils,
+            validation_sink,
+            surface_instance,
+            physical_device,
+            device: device_arc.clone(),
+            instance,
+            context,
+            entry,
             //   graphics_pipelines,
             //  render_pass,
+        };
+        configuration.name_objects();
+        configuration
+    }
+
+    // The surface belongs to the window, so it can't outlive a suspend
+    pub fn suspend(&mut self) {
+        unsafe { self.device.device_wait_idle().unwrap() };
+        self.presentation = None;
+    }
+
+    pub fn resume(&mut self, window: &Window) {
+        if self.presentation.is_some() {
+            return;
         }
+        let surface = create_surface(&self.entry, &self.instance, window);
+        self.presentation = Some(Presentation::new(
+            window,
+            Surface::new(self.surface_instance.clone(), surface),
+            self.physical_device,
+            &self.device,
+            &self.surface_instance,
+            &self.swapchain_device,
+            self.indices,
+            &self.debug_utils,
+        ));
+    }
+
+    // Replaces an out of date swapchain, keeping the surface.
+    pub fn recreate_presentation(&mut self, window: &Window) {
+        unsafe { self.device.device_wait_idle().unwrap() };
+        let Some(Presentation { surface, .. }) = self.presentation.take() else {
+            return;
+        };
+        self.presentation = Some(Presentation::new(
+            window,
+            surface,
+            self.physical_device,
+            &self.device,
+            &self.surface_instance,
+            &self.swapchain_device,
+            self.indices,
+            &self.debug_utils,
+        ));
     }
 
-    fn populate_queue(
-        deletion_queue: &mut DeletionQueue,
-        device: Arc<Device>,
-        allocated_image: Arc<AllocatedImage>,
-    ) {
-        unsafe {
-            deletion_queue.enqueue(move || {
-                device.destroy_image_view(allocated_image.clone().image_view, None)
-            })
+    fn name_objects(&self) {
+        let debug_utils = &self.debug_utils;
+        debug_utils.name(self.allocated_image.image, "draw image");
+        debug_utils.name(self.allocated_image.image_view, "draw image view");
+        debug_utils.name(*self.graphics_queue, "graphics queue");
+        debug_utils.name(self.descriptor_allocator.pool(), "draw image descriptor pool");
+        debug_utils.name(*self.descriptor_set_layout, "draw image descriptor layout");
+        debug_utils.name(self.descriptor_set, "draw image descriptor");
+        debug_utils.name(*self.pipeline_cache.cache, "pipeline cache");
+        for effect in &self.background_effects {
+            debug_utils.name(*effect.pipeline, &format!("background compute {}", effect.name));
+        }
+        if let Some(effect) = self.background_effects.first() {
+            debug_utils.name(**effect.layout, "background compute layout");
         }
     }
+}
 
-    pub fn cleanup(&self) {
-        drop(self.vma_allocator.clone());
-        let device = &self.device;
-        unsafe {
-            device.device_wait_idle().unwrap();
-            self.swapchain_device
-                .destroy_swapchain(self.swapchain, None);
-            device.destroy_device(None);
-            self.surface_instance.destroy_surface(*self.surface, None);
-            self.debug_instance
-                .destroy_debug_utils_messenger(self.debugger, None);
-            self.instance.destroy_instance(None);
+impl Drop for VkConfiguration {
+    fn drop(&mut self) {
+        unsafe { self.device.device_wait_idle().unwrap() };
+        if let Err(err) = self.pipeline_cache.save(&self.device) {
+            warn!("Failed to save pipeline cache: {err}");
         }
     }
 }
@@ -0,0 +1,329 @@
+use std::{cell::Cell, io::Error, mem::size_of, sync::Arc};
+
+use ash::{
+    vk::{
+        AccessFlags, BufferUsageFlags, DependencyFlags, DescriptorSetLayout,
+        DescriptorSetLayoutCreateFlags, DescriptorType, MemoryBarrier, Pipeline,
+        PipelineBindPoint, PipelineCache, PipelineLayout, PipelineStageFlags, PushConstantRange,
+        ShaderStageFlags,
+    },
+    Device,
+};
+use glam::Vec4;
+use vk_mem::Allocator;
+
+use crate::application::RenderContext;
+
+use super::{
+    allocated_buffer::{create_readback_buffer, AllocatedBuffer},
+    components::{
+        create_compute_pipeline, create_compute_pipeline_layout, write_storage_buffer,
+        DescriptorLayoutBuilder, ShaderRegistry,
+    },
+    configuration::MAX_FRAMES,
+    debug_utils::DebugUtils,
+    handles::Owned,
+    indirect::IndirectDraws,
+    ring_buffer::RingAllocation,
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+// Where cull.comp finds its buffers, the camera is at CAMERA_SET as usual
+const CULL_SET: u32 = 1;
+
+// One per `IndirectBatch`, std430:
+//
+// struct Batch { vec4 sphere; uint firstCommand; uint commandCount; uint firstInstance; uint padding; };
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct CullBatch {
+    // Bounding sphere of the mesh in its own space, w is the radius
+    sphere: Vec4,
+    first_command: u32,
+    command_count: u32,
+    first_instance: u32,
+    padding: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullingStats {
+    pub tested: u32,
+    pub visible: u32,
+}
+
+impl CullingStats {
+    pub fn culled(&self) -> u32 {
+        self.tested.saturating_sub(self.visible)
+    }
+}
+
+// Tests every instance's bounding sphere against the camera frustum on the GPU and
+// compacts the survivors into a fresh instance buffer, counting them into the
+// instanceCount of each indirect command. Counts are read back once the frame that
+// produced them has finished, so they trail by MAX_FRAMES frames.
+pub struct FrustumCuller {
+    // One per frame in flight, host visible so the counts can be read directly
+    stats_buffers: Vec<AllocatedBuffer>,
+    stats: Cell<CullingStats>,
+    pipeline: Owned<Pipeline>,
+    pipeline_layout: Owned<PipelineLayout>,
+    descriptor_layout: Owned<DescriptorSetLayout>,
+}
+
+impl FrustumCuller {
+    pub fn new(
+        device: &Arc<Device>,
+        allocator: &Arc<Allocator>,
+        camera_layout: DescriptorSetLayout,
+        shader_registry: &ShaderRegistry,
+        pipeline_cache: PipelineCache,
+        debug_utils: &DebugUtils,
+    ) -> Result<Self, Error> {
+        let mut layout_builder = DescriptorLayoutBuilder::new();
+        for binding in 0..6 {
+            layout_builder.add_binding(binding, DescriptorType::STORAGE_BUFFER);
+        }
+        let descriptor_layout = layout_builder.build(
+            device.clone(),
+            ShaderStageFlags::COMPUTE,
+            DescriptorSetLayoutCreateFlags::empty(),
+        );
+        let push_constant_ranges = [PushConstantRange::default()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(size_of::<u32>() as u32)];
+        let pipeline_layout = create_compute_pipeline_layout(
+            device.clone(),
+            &[camera_layout, *descriptor_layout],
+            &push_constant_ranges,
+        );
+        let pipeline = create_compute_pipeline(
+            device.clone(),
+            "cull",
+            *pipeline_layout,
+            shader_registry,
+            pipeline_cache,
+        )?;
+        let stats_buffers = (0..MAX_FRAMES)
+            .map(|i| {
+                let mut buffer = create_readback_buffer(
+                    allocator,
+                    size_of::<CullingStats>() as u64,
+                    BufferUsageFlags::STORAGE_BUFFER,
+                )?;
+                buffer.write(0, as_bytes(&CullingStats::default()));
+                debug_utils.name(buffer.buffer, &format!("frame {i} culling stats"));
+                Ok(buffer)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        debug_utils.name(*descriptor_layout, "culling descriptor layout");
+        debug_utils.name(*pipeline_layout, "culling pipeline layout");
+        debug_utils.name(*pipeline, "culling pipeline");
+        Ok(Self {
+            stats_buffers,
+            stats: Cell::new(CullingStats::default()),
+            pipeline,
+            pipeline_layout,
+            descriptor_layout,
+        })
+    }
+
+    // Picks up the counts `frame_index` wrote last time round and zeroes them for this
+    // frame. Only call once the frame's render fence has been waited on.
+    pub fn begin_frame(&mut self, frame_index: usize) {
+        self.stats.set(take_stats(&mut self.stats_buffers[frame_index]));
+    }
+
+    // What the most recently finished frame counted, summed over every `cull` in it.
+    pub fn stats(&self) -> CullingStats {
+        self.stats.get()
+    }
+
+    // Records the culling dispatch and returns draws that read its output. `draws` is
+    // left as it was, drawing it still draws everything.
+    pub fn cull<'a>(&self, context: &RenderContext, draws: &IndirectDraws<'a>) -> IndirectDraws<'a> {
+        let ring = context.ring;
+        let batches: Vec<CullBatch> = draws
+            .batches
+            .iter()
+            .map(|batch| {
+                let bounds = batch.mesh.bounds;
+                CullBatch {
+                    sphere: bounds.center().extend(bounds.extent().length() / 2.0),
+                    first_command: batch.first_command,
+                    command_count: batch.command_count,
+                    first_instance: batch.first_instance,
+                    padding: 0,
+                }
+            })
+            .collect();
+        let instance_batches: Vec<u32> = draws
+            .batches
+            .iter()
+            .enumerate()
+            .flat_map(|(index, batch)| (0..batch.instance_count).map(move |_| index as u32))
+            .collect();
+        let commands: Vec<_> = draws
+            .batches
+            .iter()
+            .flat_map(|batch| batch.commands(0))
+            .collect();
+        let culled = IndirectDraws {
+            batches: draws.batches.clone(),
+            instances: ring.allocate(draws.instances.size),
+            commands: ring.push(&commands),
+            instance_count: draws.instance_count,
+            command_count: draws.command_count,
+        };
+        if draws.instance_count == 0 {
+            return culled;
+        }
+
+        let stats_buffer = &self.stats_buffers[context.frame_index];
+        let stats = RingAllocation {
+            buffer: stats_buffer.buffer,
+            offset: 0,
+            size: stats_buffer.size,
+        };
+        let buffers = [
+            draws.instances,
+            ring.push(&instance_batches),
+            ring.push(&batches),
+            culled.instances,
+            culled.commands,
+            stats,
+        ];
+        let device = context.device;
+        let command_buffer = context.command_buffer;
+        let descriptor_set = context.allocate_descriptor_set(*self.descriptor_layout);
+        for (binding, buffer) in buffers.iter().enumerate() {
+            write_storage_buffer(
+                device,
+                descriptor_set,
+                binding as u32,
+                buffer.buffer,
+                buffer.offset,
+                buffer.size,
+            );
+        }
+        let label = context.debug_utils.label(command_buffer, "frustum culling");
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, *self.pipeline);
+        }
+        context.bind_camera(PipelineBindPoint::COMPUTE, *self.pipeline_layout);
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::COMPUTE,
+                *self.pipeline_layout,
+                CULL_SET,
+                &[descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                *self.pipeline_layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                &draws.instance_count.to_ne_bytes(),
+            );
+            device.cmd_dispatch(
+                command_buffer,
+                draws.instance_count.div_ceil(WORKGROUP_SIZE),
+                1,
+                1,
+            );
+            // The fence alone doesn't make the counts visible to `begin_frame`
+            let barrier = MemoryBarrier::default()
+                .src_access_mask(AccessFlags::SHADER_WRITE)
+                .dst_access_mask(AccessFlags::HOST_READ);
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::HOST,
+                DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
+        }
+        drop(label);
+        culled
+    }
+}
+
+// Where a frame's counts live, a host visible buffer outside of tests.
+trait StatsStorage {
+    fn read(&self, data: &mut [u8]);
+    fn write(&mut self, data: &[u8]);
+}
+
+impl StatsStorage for AllocatedBuffer {
+    fn read(&self, data: &mut [u8]) {
+        AllocatedBuffer::read(self, 0, data);
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        AllocatedBuffer::write(self, 0, data);
+    }
+}
+
+// Reads the counts before zeroing them, the other way round every frame would report
+// nothing.
+fn take_stats(storage: &mut impl StatsStorage) -> CullingStats {
+    let mut stats = CullingStats::default();
+    storage.read(as_bytes_mut(&mut stats));
+    storage.write(as_bytes(&CullingStats::default()));
+    stats
+}
+
+fn as_bytes(stats: &CullingStats) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(
+            stats as *const CullingStats as *const u8,
+            size_of::<CullingStats>(),
+        )
+    }
+}
+
+fn as_bytes_mut(stats: &mut CullingStats) -> &mut [u8] {
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            stats as *mut CullingStats as *mut u8,
+            size_of::<CullingStats>(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl StatsStorage for Vec<u8> {
+        fn read(&self, data: &mut [u8]) {
+            data.copy_from_slice(self);
+        }
+
+        fn write(&mut self, data: &[u8]) {
+            self.copy_from_slice(data);
+        }
+    }
+
+    #[test]
+    fn begin_frame_reads_the_counts_before_zeroing_them() {
+        let counted = CullingStats {
+            tested: 100,
+            visible: 42,
+        };
+        let mut storage = as_bytes(&counted).to_vec();
+        // Zeroing first would hand back zeros here
+        assert_eq!(take_stats(&mut storage), counted);
+        assert_eq!(counted.culled(), 58);
+        assert_eq!(storage, as_bytes(&CullingStats::default()));
+
+        // The next frame sees only what was counted since
+        assert_eq!(take_stats(&mut storage), CullingStats::default());
+    }
+}
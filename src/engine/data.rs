@@ -1,23 +1,43 @@
-use ash::vk::{CommandBuffer, CommandPool, Fence, Semaphore};
+use std::sync::Arc;
 
-use super::deletion_queue::DeletionQueue;
+use ash::vk::{CommandBuffer, CommandPool, DescriptorSet, Fence, Semaphore};
+
+use super::{
+    allocated_buffer::AllocatedBuffer, components::DescriptorAllocato,
+    deletion_queue::DeletionQueue, handles::Owned, ring_buffer::RingBuffer,
+};
 
 pub struct FrameData {
-    pub command_pool: CommandPool,
+    // Shared by every frame, only held so it outlives the command buffers
+    #[allow(dead_code)]
+    pub command_pool: Arc<Owned<CommandPool>>,
     pub command_buffer: CommandBuffer,
-    pub swapchain_semaphore: Semaphore,
-    pub render_semaphore: Semaphore,
-    pub render_fence: Fence,
+    pub swapchain_semaphore: Owned<Semaphore>,
+    pub render_semaphore: Owned<Semaphore>,
+    pub render_fence: Owned<Fence>,
+    // Host visible, rewritten every frame once the render fence has been waited on
+    pub camera_buffer: AllocatedBuffer,
+    pub camera_set: DescriptorSet,
+    // Reset together with the deletion queue
+    pub ring: RingBuffer,
+    // Sets for this frame's passes, the whole pool is reset along with the ring
+    pub descriptor_allocator: DescriptorAllocato,
+    // Flushed once the render fence of this frame has been waited on
     pub deletion_queue: DeletionQueue
 }
 
 impl FrameData {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        command_pool: CommandPool,
+        command_pool: Arc<Owned<CommandPool>>,
         command_buffer: CommandBuffer,
-        swapchain_semaphore: Semaphore,
-        render_semaphore: Semaphore,
-        render_fence: Fence,
+        swapchain_semaphore: Owned<Semaphore>,
+        render_semaphore: Owned<Semaphore>,
+        render_fence: Owned<Fence>,
+        camera_buffer: AllocatedBuffer,
+        camera_set: DescriptorSet,
+        ring: RingBuffer,
+        descriptor_allocator: DescriptorAllocato,
     ) -> Self {
         Self {
             command_pool,
@@ -25,6 +45,10 @@ impl FrameData {
             swapchain_semaphore,
             render_semaphore,
             render_fence,
+            camera_buffer,
+            camera_set,
+            ring,
+            descriptor_allocator,
             deletion_queue: DeletionQueue::new()
         }
     }
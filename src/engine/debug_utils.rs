@@ -0,0 +1,102 @@
+#[cfg(feature = "debug-utils")]
+use std::ffi::CString;
+
+use ash::{
+    vk::{CommandBuffer, Handle},
+    Device, Instance,
+};
+#[cfg(feature = "debug-utils")]
+use ash::{
+    ext,
+    vk::{DebugUtilsLabelEXT, DebugUtilsObjectNameInfoEXT},
+};
+#[cfg(feature = "debug-utils")]
+use log::warn;
+
+use super::object_tracker;
+
+pub const LABEL_COLOR: [f32; 4] = [0.4, 0.6, 1.0, 1.0];
+
+// Names objects and labels command buffer regions for validation messages and
+// capture tools. Every call is a no-op when VK_EXT_debug_utils is not enabled on the
+// instance, and the whole thing compiles away without the `debug-utils` feature.
+#[derive(Clone)]
+pub struct DebugUtils {
+    #[cfg(feature = "debug-utils")]
+    device: Option<ext::debug_utils::Device>,
+}
+
+impl DebugUtils {
+    #[allow(unused_variables)]
+    pub fn new(instance: &Instance, device: &Device, enabled: bool) -> Self {
+        Self {
+            #[cfg(feature = "debug-utils")]
+            device: enabled.then(|| ext::debug_utils::Device::new(instance, device)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        #[cfg(feature = "debug-utils")]
+        return self.device.is_some();
+        #[cfg(not(feature = "debug-utils"))]
+        return false;
+    }
+
+    // The name also shows up in the leak report when the object is tracked.
+    pub fn name<H: Handle + Copy>(&self, handle: H, name: &str) {
+        object_tracker::set_name(handle, name);
+        self.set_object_name(handle, name);
+    }
+
+    #[allow(unused_variables)]
+    fn set_object_name<H: Handle>(&self, handle: H, name: &str) {
+        #[cfg(feature = "debug-utils")]
+        if let Some(device) = &self.device {
+            let name = CString::new(name).unwrap_or_default();
+            let name_info = DebugUtilsObjectNameInfoEXT::default()
+                .object_handle(handle)
+                .object_name(&name);
+            if let Err(err) = unsafe { device.set_debug_utils_object_name(&name_info) } {
+                warn!("Failed to name {name:?}: {err}");
+            }
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn begin_label(&self, command_buffer: CommandBuffer, name: &str, color: [f32; 4]) {
+        #[cfg(feature = "debug-utils")]
+        if let Some(device) = &self.device {
+            let name = CString::new(name).unwrap_or_default();
+            let label = DebugUtilsLabelEXT::default().label_name(&name).color(color);
+            unsafe { device.cmd_begin_debug_utils_label(command_buffer, &label) };
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn end_label(&self, command_buffer: CommandBuffer) {
+        #[cfg(feature = "debug-utils")]
+        if let Some(device) = &self.device {
+            unsafe { device.cmd_end_debug_utils_label(command_buffer) };
+        }
+    }
+
+    // Opens a label that is closed when the returned guard is dropped.
+    pub fn label(&self, command_buffer: CommandBuffer, name: &str) -> DebugLabel<'_> {
+        self.begin_label(command_buffer, name, LABEL_COLOR);
+        DebugLabel {
+            debug_utils: self,
+            command_buffer,
+        }
+    }
+}
+
+pub struct DebugLabel<'a> {
+    debug_utils: &'a DebugUtils,
+    command_buffer: CommandBuffer,
+}
+
+impl Drop for DebugLabel<'_> {
+    fn drop(&mut self) {
+        self.debug_utils.end_label(self.command_buffer);
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::VecDeque;
 
 pub struct DeletionQueue {
-    queue: VecDeque<Box<dyn Fn() + Send + Sync>>, 
+    queue: VecDeque<Box<dyn FnOnce() + Send + Sync>>, 
 }
 
 
@@ -13,10 +13,17 @@ impl DeletionQueue {
     }
 
     pub fn enqueue<T>(&mut self, func : T) 
-    where T: Fn() + 'static + Send + Sync
+    where T: FnOnce() + 'static + Send + Sync
     {
         self.queue.push_back(Box::new(func));
     }
 
+    // Newest first, so objects go before whatever they were created from
+    pub fn flush(&mut self) {
+        while let Some(func) = self.queue.pop_back() {
+            func();
+        }
+    }
+
 }
   
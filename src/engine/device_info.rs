@@ -0,0 +1,143 @@
+use ash::vk::{
+    Extent2D, PhysicalDeviceFeatures, PhysicalDeviceProperties, PresentModeKHR, SurfaceFormatKHR,
+    TRUE,
+};
+
+use super::{
+    components::{device_extensions, QueueFamilyIndices},
+    configuration::VkConfiguration,
+};
+
+// Snapshot of the device and swapchain the engine runs on, shown by the inspector.
+#[derive(Default)]
+pub struct DeviceInfo {
+    pub properties: PhysicalDeviceProperties,
+    pub enabled_extensions: Vec<String>,
+    pub enabled_features: Vec<String>,
+    pub queue_family_indices: QueueFamilyIndices,
+    pub swapchain_format: SurfaceFormatKHR,
+    pub present_mode: PresentModeKHR,
+    pub swapchain_extent: Extent2D,
+    pub swapchain_image_count: usize,
+}
+
+// Names of the Bool32 fields of `$features` that are set, in declaration order.
+macro_rules! enabled_features {
+    ($features:expr, [$($field:ident),* $(,)?]) => {
+        [$((stringify!($field), $features.$field)),*]
+            .into_iter()
+            .filter(|(_, value)| *value == TRUE)
+            .map(|(name, _)| name.to_string())
+            .collect()
+    };
+}
+
+fn enabled_feature_names(features: &PhysicalDeviceFeatures) -> Vec<String> {
+    enabled_features!(
+        features,
+        [
+            robust_buffer_access,
+            full_draw_index_uint32,
+            image_cube_array,
+            independent_blend,
+            geometry_shader,
+            tessellation_shader,
+            sample_rate_shading,
+            dual_src_blend,
+            logic_op,
+            multi_draw_indirect,
+            draw_indirect_first_instance,
+            depth_clamp,
+            depth_bias_clamp,
+            fill_mode_non_solid,
+            depth_bounds,
+            wide_lines,
+            large_points,
+            alpha_to_one,
+            multi_viewport,
+            sampler_anisotropy,
+            texture_compression_etc2,
+            texture_compression_astc_ldr,
+            texture_compression_bc,
+            occlusion_query_precise,
+            pipeline_statistics_query,
+            vertex_pipeline_stores_and_atomics,
+            fragment_stores_and_atomics,
+            shader_tessellation_and_geometry_point_size,
+            shader_image_gather_extended,
+            shader_storage_image_extended_formats,
+            shader_storage_image_multisample,
+            shader_storage_image_read_without_format,
+            shader_storage_image_write_without_format,
+            shader_uniform_buffer_array_dynamic_indexing,
+            shader_sampled_image_array_dynamic_indexing,
+            shader_storage_buffer_array_dynamic_indexing,
+            shader_storage_image_array_dynamic_indexing,
+            shader_clip_distance,
+            shader_cull_distance,
+            shader_float64,
+            shader_int64,
+            shader_int16,
+            shader_resource_residency,
+            shader_resource_min_lod,
+            sparse_binding,
+            sparse_residency_buffer,
+            sparse_residency_image2_d,
+            sparse_residency_image3_d,
+            sparse_residency2_samples,
+            sparse_residency4_samples,
+            sparse_residency8_samples,
+            sparse_residency16_samples,
+            sparse_residency_aliased,
+            variable_multisample_rate,
+            inherited_queries,
+        ]
+    )
+}
+
+impl DeviceInfo {
+    pub fn new(configuration: &VkConfiguration) -> Self {
+        let instance = &configuration.instance;
+        let physical_device = configuration.physical_device;
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let features = unsafe { instance.get_physical_device_features(physical_device) };
+        let presentation = configuration.presentation.as_ref();
+        Self {
+            properties,
+            enabled_extensions: device_extensions()
+                .iter()
+                .map(|extension| extension.to_string_lossy().into_owned())
+                .collect(),
+            enabled_features: enabled_feature_names(&features),
+            queue_family_indices: configuration.indices,
+            swapchain_format: presentation
+                .map(|p| p.swapchain_support_details.clone().choose_swapchain_format())
+                .unwrap_or_default(),
+            present_mode: presentation
+                .map(|p| p.swapchain_support_details.clone().choose_swapchain_present_mode())
+                .unwrap_or_default(),
+            swapchain_extent: presentation.map(|p| p.extent).unwrap_or_default(),
+            swapchain_image_count: presentation.map_or(0, |p| p.images.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_only_enabled_features() {
+        let features = PhysicalDeviceFeatures {
+            robust_buffer_access: TRUE,
+            multi_draw_indirect: TRUE,
+            inherited_queries: TRUE,
+            ..Default::default()
+        };
+        assert_eq!(
+            enabled_feature_names(&features),
+            ["robust_buffer_access", "multi_draw_indirect", "inherited_queries"]
+        );
+        assert!(enabled_feature_names(&PhysicalDeviceFeatures::default()).is_empty());
+    }
+}
@@ -0,0 +1,31 @@
+use ash::{
+    vk::{CommandBuffer, RenderPass},
+    Device,
+};
+use egui::PaintCallbackInfo;
+
+// Everything a paint callback needs to record into the egui render pass. Viewport
+// and scissor are already set from the callback rect and clip rect.
+pub struct CallbackContext<'a> {
+    pub device: &'a Device,
+    pub command_buffer: CommandBuffer,
+    pub render_pass: RenderPass,
+    pub frame_index: usize,
+}
+
+type Callback = dyn Fn(PaintCallbackInfo, &CallbackContext) + Send + Sync;
+
+// Put into `egui::PaintCallback::callback`, the egui renderer ignores callbacks of any other type.
+pub struct CallbackFn {
+    f: Box<Callback>,
+}
+
+impl CallbackFn {
+    pub fn new<F: Fn(PaintCallbackInfo, &CallbackContext) + Send + Sync + 'static>(f: F) -> Self {
+        Self { f: Box::new(f) }
+    }
+
+    pub fn call(&self, info: PaintCallbackInfo, context: &CallbackContext) {
+        (self.f)(info, context)
+    }
+}
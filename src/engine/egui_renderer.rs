@@ -1,191 +1,862 @@
-use std::sync::Arc;
+use std::{collections::HashMap, mem::size_of_val, slice, sync::Arc, time::Duration};
 
 use ash::{
     vk::{
-        BufferCreateFlags, BufferCreateInfo, BufferUsageFlags, ClearColorValue,
-        ClearDepthStencilValue, ClearValue, CommandBuffer, CommandBufferUsageFlags, CommandPool,
-        Extent2D, Fence, Framebuffer, FramebufferCreateInfo, MemoryPropertyFlags, MemoryType,
-        Offset2D, Queue, Rect2D, RenderPassBeginInfo, SharingMode, SubpassContents, SurfaceKHR,
+        BufferImageCopy, CommandBuffer, DescriptorImageInfo,
+        DescriptorPoolCreateFlags, DescriptorSet, DescriptorSetLayout,
+        DescriptorSetLayoutCreateFlags, DescriptorType, Extent2D, Extent3D, Filter, Format,
+        Framebuffer, FramebufferCreateInfo, ImageAspectFlags, ImageLayout,
+        ImageSubresourceLayers, ImageUsageFlags, ImageView, IndexType, Offset2D, Offset3D,
+        Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout, Rect2D, RenderPass,
+        RenderPassBeginInfo, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode,
+        ShaderStageFlags, SubpassContents, Viewport, WriteDescriptorSet,
     },
     Device,
 };
 use derive_setters::Setters;
-use egui::{ClippedPrimitive, FullOutput, RawInput};
-use log::debug;
-use vk_mem::{Alloc, Allocation, AllocationCreateInfo, Allocator, MemoryUsage};
-use winit::window::Window;
+use egui::{
+    epaint::{ImageDelta, Primitive, Vertex},
+    ClippedPrimitive, Context, PaintCallback, PaintCallbackInfo, ImageData, Rect, TextureFilter, TextureId, TextureOptions,
+    TextureWrapMode, TexturesDelta, ViewportId,
+};
+use egui_winit::EventResponse;
+use log::warn;
+use vk_mem::{Alloc, AllocationCreateInfo, Allocator, MemoryUsage};
+use winit::{event::WindowEvent, window::Window};
 
-use crate::engine::command_buffers::{allocate_command_buffer, create_command_pool};
-use crate::engine::components::EGUIConfig;
-use crate::engine::deletion_queue::DeletionQueue;
-use crate::engine::sync_objects::*;
+use crate::engine::components::{
+    create_egui_pipeline, DescriptorAllocato, DescriptorLayoutBuilder, EGUIConfig, PoolSizeRatio,
+    ShaderRegistry,
+};
+use crate::engine::configuration::MAX_FRAMES;
+use crate::engine::image_ops::{
+    copy_image_to_image, image_create_info, image_transition, image_view_create_info,
+};
+
+use super::{
+    allocated_buffer::{create_staging_buffer, AllocatedBuffer},
+    allocated_image::AllocatedImage,
+    debug_utils::DebugUtils,
+    egui_callback::{CallbackContext, CallbackFn},
+    handles::Owned,
+    ring_buffer::{RingAllocation, RingBuffer},
+    memory_stats::{track_allocation, AllocationCategory},
+};
 
-use super::{allocated_image::AllocatedImage, command_buffers};
+const MAX_TEXTURES: u32 = 1024;
 
 pub trait Renderer {
-    fn draw(&self, allocated_image: Arc<AllocatedImage>) -> CommandBuffer;
-    fn buffer_allocation(&mut self, allocator: Arc<Allocator>, extent: Extent2D) -> Allocation;
+    fn context(&self) -> &Context;
+    fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> EventResponse;
+    // Runs one egui pass and returns how long egui is happy to wait before the next one.
+    fn prepare_frame(&mut self, window: &Window, ui: &mut dyn FnMut(&Context)) -> Duration;
+    fn draw(
+        &mut self,
+        command_buffer: CommandBuffer,
+        frame_index: usize,
+        allocated_image: Arc<AllocatedImage>,
+        ring: &RingBuffer,
+    );
+}
+
+pub struct EGUITexture {
+    pub image: AllocatedImage,
+    pub descriptor_set: DescriptorSet,
+}
+
+// An engine owned image shown through egui. `layout` is the layout the image is in
+// when the egui pass runs, it is moved to SHADER_READ_ONLY_OPTIMAL around the pass.
+pub struct UserTexture {
+    pub image: Arc<AllocatedImage>,
+    pub layout: ImageLayout,
+    descriptor_set: DescriptorSet,
+    // The egui pass cannot sample its own color attachment, so when `image` is the
+    // render target it is copied here first and the copy is sampled instead.
+    mirror: Option<AllocatedImage>,
+}
+
+// Resources that belong to one frame in flight, they are only touched again
+// once the render fence of that frame has been waited on.
+#[derive(Default)]
+struct EGUIFrame {
+    // This frame's meshes, back to back in the frame's ring
+    vertices: Option<RingAllocation>,
+    indices: Option<RingAllocation>,
+    staging_buffers: Vec<AllocatedBuffer>,
+    retired_textures: Vec<EGUITexture>,
+    retired_user_textures: Vec<UserTexture>,
 }
 
+// Fields are dropped in declaration order, so everything created from the render
+// pass, the pipeline and the descriptor pool comes before them.
 #[derive(Setters)]
 pub struct EGUIRenderer {
-    configuration: EGUIConfig,
-    fence: Fence,
-    command_pool: CommandPool,
-    command_buffer: CommandBuffer,
-    deletion_queue: DeletionQueue,
+    textures: HashMap<TextureId, EGUITexture>,
+    user_textures: HashMap<TextureId, UserTexture>,
+    next_user_texture_id: u64,
+    frames: Vec<EGUIFrame>,
+    framebuffer: Option<(ImageView, Owned<Framebuffer>)>,
+    samplers: HashMap<TextureOptions, Owned<Sampler>>,
+    pipeline: Owned<Pipeline>,
+    pipeline_layout: Owned<PipelineLayout>,
+    descriptor_allocator: DescriptorAllocato,
+    descriptor_set_layout: Owned<DescriptorSetLayout>,
     render_information: RenderInformation,
+    debug_utils: DebugUtils,
+    allocator: Arc<Allocator>,
+    configuration: EGUIConfig,
 }
 
 #[derive(Default)]
 pub struct RenderInformation {
-    pub vertices: Vec<egui::epaint::Vertex>,
-    pub indices: Vec<u32>,
-    pub extent: ash::vk::Extent2D,
+    pub primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: TexturesDelta,
+    pub pixels_per_point: f32,
 }
 
 pub struct ConfigurationParameter<'a> {
     pub device: Arc<Device>,
     pub window: &'a Window,
-    pub gfx_queue: Arc<Queue>,
-    pub surface: Arc<SurfaceKHR>,
+    pub allocator: Arc<Allocator>,
+    pub shader_registry: &'a ShaderRegistry,
+    pub pipeline_cache: PipelineCache,
+    pub debug_utils: DebugUtils,
+}
+
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) }
+}
+
+fn create_sampler(device: &Device, options: TextureOptions) -> Sampler {
+    let filter = |filter: TextureFilter| match filter {
+        TextureFilter::Nearest => Filter::NEAREST,
+        TextureFilter::Linear => Filter::LINEAR,
+    };
+    let address_mode = match options.wrap_mode {
+        TextureWrapMode::ClampToEdge => SamplerAddressMode::CLAMP_TO_EDGE,
+        TextureWrapMode::Repeat => SamplerAddressMode::REPEAT,
+        TextureWrapMode::MirroredRepeat => SamplerAddressMode::MIRRORED_REPEAT,
+    };
+    let create_info = SamplerCreateInfo::default()
+        .mag_filter(filter(options.magnification))
+        .min_filter(filter(options.minification))
+        .mipmap_mode(SamplerMipmapMode::LINEAR)
+        .address_mode_u(address_mode)
+        .address_mode_v(address_mode)
+        .address_mode_w(address_mode);
+    unsafe { device.create_sampler(&create_info, None).unwrap() }
 }
 
 impl EGUIRenderer {
     pub fn new(parameter: ConfigurationParameter, gfx_queue_family_idx: u32) -> EGUIRenderer {
-        let fence = create_fence(&parameter.device);
-        let command_pool = create_command_pool(&parameter.device, gfx_queue_family_idx);
-        let command_buffer = allocate_command_buffer(&parameter.device, command_pool);
-        let mut deletion_queue = DeletionQueue::new();
         let device = parameter.device.clone();
-        deletion_queue.enqueue(move || unsafe { device.destroy_command_pool(command_pool, None) });
+        let configuration = EGUIConfig::new(
+            parameter.device,
+            parameter.window,
+            gfx_queue_family_idx,
+        );
+
+        let mut descriptor_layout_builder = DescriptorLayoutBuilder::new();
+        descriptor_layout_builder.add_binding(0, DescriptorType::SAMPLED_IMAGE);
+        descriptor_layout_builder.add_binding(1, DescriptorType::SAMPLER);
+        let descriptor_set_layout = descriptor_layout_builder.build(
+            device.clone(),
+            ShaderStageFlags::FRAGMENT,
+            DescriptorSetLayoutCreateFlags::empty(),
+        );
+        let descriptor_allocator = DescriptorAllocato::new(
+            device.clone(),
+            MAX_TEXTURES,
+            vec![
+                PoolSizeRatio::new(DescriptorType::SAMPLED_IMAGE, 1.0),
+                PoolSizeRatio::new(DescriptorType::SAMPLER, 1.0),
+            ],
+            DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+        );
+        let (pipeline_layout, pipeline) = create_egui_pipeline(
+            device,
+            &configuration.render_pass,
+            *descriptor_set_layout,
+            parameter.shader_registry,
+            parameter.pipeline_cache,
+        )
+        .unwrap();
+        let debug_utils = parameter.debug_utils;
+        debug_utils.name(*configuration.render_pass, "egui render pass");
+        debug_utils.name(*descriptor_set_layout, "egui descriptor layout");
+        debug_utils.name(*pipeline_layout, "egui pipeline layout");
+        debug_utils.name(*pipeline, "egui pipeline");
+        debug_utils.name(descriptor_allocator.pool(), "egui descriptor pool");
+
         Self {
-            fence,
-            command_pool,
-            command_buffer,
-            configuration: EGUIConfig::new(
-                parameter.device,
-                parameter.window,
-                parameter.gfx_queue,
-                gfx_queue_family_idx,
-                parameter.surface,
-            ),
-            deletion_queue,
+            textures: HashMap::new(),
+            user_textures: HashMap::new(),
+            next_user_texture_id: 0,
+            frames: (0..MAX_FRAMES).map(|_| EGUIFrame::default()).collect(),
+            framebuffer: None,
+            samplers: HashMap::new(),
+            pipeline,
+            pipeline_layout,
+            descriptor_allocator,
+            descriptor_set_layout,
             render_information: Default::default(),
+            debug_utils,
+            allocator: parameter.allocator,
+            configuration,
         }
     }
 
-    fn begin_render_pass(&self, allocated_image: Arc<AllocatedImage>) {
-        unsafe {
-            let image_views = vec![allocated_image.image_view];
-            let framebuffer_create_info = FramebufferCreateInfo::default()
-                .width(self.render_information.extent.width)
-                .height(self.render_information.extent.height)
-                .render_pass(self.configuration.render_pass)
-                .attachments(&image_views)
-                .layers(1);
-            let framebuffer = self
-                .configuration
+    fn sampler(&mut self, options: TextureOptions) -> Sampler {
+        let device = &self.configuration.device;
+        let debug_utils = &self.debug_utils;
+        **self.samplers.entry(options).or_insert_with(|| {
+            let sampler = Owned::new(device.clone(), create_sampler(device, options));
+            debug_utils.name(*sampler, &format!("egui sampler {options:?}"));
+            sampler
+        })
+    }
+
+    fn create_image(&self, extent: Extent3D, format: Format, usage: ImageUsageFlags) -> AllocatedImage {
+        let image_create_info = image_create_info(format, usage, extent);
+        let allocation_create_info = AllocationCreateInfo {
+            usage: MemoryUsage::AutoPreferDevice,
+            ..Default::default()
+        };
+        let (image, allocation) = unsafe {
+            self.allocator
+                .create_image(&image_create_info, &allocation_create_info)
+                .unwrap()
+        };
+        track_allocation(&self.allocator, &allocation, AllocationCategory::Image);
+        let image_view = unsafe {
+            self.configuration
                 .device
+                .create_image_view(
+                    &image_view_create_info(image, format, ImageAspectFlags::COLOR),
+                    None,
+                )
+                .unwrap()
+        };
+        AllocatedImage::new(
+            self.configuration.device.clone(),
+            self.allocator.clone(),
+            image,
+            image_view,
+            allocation,
+            extent,
+            format,
+        )
+    }
+
+    fn write_image_descriptor(&self, descriptor_set: DescriptorSet, image_view: ImageView) {
+        let image_info = [DescriptorImageInfo::default()
+            .image_view(image_view)
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+        let write = WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(DescriptorType::SAMPLED_IMAGE)
+            .image_info(&image_info);
+        unsafe { self.configuration.device.update_descriptor_sets(&[write], &[]) };
+    }
+
+    fn allocate_descriptor_set(&mut self, image_view: ImageView, options: TextureOptions) -> DescriptorSet {
+        let device = self.configuration.device.clone();
+        let descriptor_set = self
+            .descriptor_allocator
+            .allocate(&device, &[*self.descriptor_set_layout]);
+        let sampler_info = [DescriptorImageInfo::default().sampler(self.sampler(options))];
+        let write = WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(1)
+            .descriptor_type(DescriptorType::SAMPLER)
+            .image_info(&sampler_info);
+        unsafe { device.update_descriptor_sets(&[write], &[]) };
+        self.write_image_descriptor(descriptor_set, image_view);
+        descriptor_set
+    }
+
+    fn create_texture(&mut self, width: u32, height: u32, options: TextureOptions) -> EGUITexture {
+        let image = self.create_image(
+            Extent3D::default().width(width).height(height).depth(1),
+            Format::R8G8B8A8_SRGB,
+            ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST,
+        );
+        let descriptor_set = self.allocate_descriptor_set(image.image_view, options);
+        EGUITexture {
+            image,
+            descriptor_set,
+        }
+    }
+
+    // The images go with the texture, only the descriptor set has to be handed back
+    fn destroy_texture(&self, texture: EGUITexture) {
+        self.descriptor_allocator
+            .free(&self.configuration.device, &[texture.descriptor_set]);
+    }
+
+    fn destroy_user_texture(&self, texture: UserTexture) {
+        self.descriptor_allocator
+            .free(&self.configuration.device, &[texture.descriptor_set]);
+    }
+
+    pub fn render_pass(&self) -> RenderPass {
+        *self.configuration.render_pass
+    }
+
+    pub fn register_user_texture(
+        &mut self,
+        image: Arc<AllocatedImage>,
+        layout: ImageLayout,
+        options: TextureOptions,
+    ) -> TextureId {
+        let id = TextureId::User(self.next_user_texture_id);
+        self.next_user_texture_id += 1;
+        let descriptor_set = self.allocate_descriptor_set(image.image_view, options);
+        self.debug_utils
+            .name(descriptor_set, &format!("egui user texture {id:?}"));
+        self.user_textures.insert(
+            id,
+            UserTexture {
+                image,
+                layout,
+                descriptor_set,
+                mirror: None,
+            },
+        );
+        id
+    }
+
+    // The texture stays alive until the frames that may still sample it are done.
+    pub fn free_user_texture(&mut self, id: TextureId) {
+        if self.user_textures.contains_key(&id) {
+            self.render_information.textures_delta.free.push(id);
+        }
+    }
+
+    fn used_user_textures(&self) -> Vec<TextureId> {
+        let mut ids: Vec<TextureId> = self
+            .render_information
+            .primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) if self.user_textures.contains_key(&mesh.texture_id) => {
+                    Some(mesh.texture_id)
+                }
+                _ => None,
+            })
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    fn copy_to_mirror(&mut self, command_buffer: CommandBuffer, id: TextureId, target: &AllocatedImage) {
+        if self.user_textures[&id].mirror.is_none() {
+            let mirror = self.create_image(
+                target.extent,
+                target.image_format,
+                ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST,
+            );
+            self.debug_utils
+                .name(mirror.image, &format!("egui user texture {id:?} mirror"));
+            self.debug_utils
+                .name(mirror.image_view, &format!("egui user texture {id:?} mirror view"));
+            let texture = &self.user_textures[&id];
+            self.write_image_descriptor(texture.descriptor_set, mirror.image_view);
+            self.user_textures.get_mut(&id).unwrap().mirror = Some(mirror);
+        }
+        let mirror = self.user_textures[&id].mirror.as_ref().unwrap().image;
+        let device = &self.configuration.device;
+        let queue_family_index = self.configuration.gfx_queue_family_index;
+        let extent = Extent2D::default()
+            .width(target.extent.width)
+            .height(target.extent.height);
+        image_transition(
+            device,
+            command_buffer,
+            queue_family_index,
+            target.image,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+        );
+        image_transition(
+            device,
+            command_buffer,
+            queue_family_index,
+            mirror,
+            ImageLayout::UNDEFINED,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+        );
+        copy_image_to_image(device, command_buffer, target.image, mirror, extent, extent);
+        image_transition(
+            device,
+            command_buffer,
+            queue_family_index,
+            mirror,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        image_transition(
+            device,
+            command_buffer,
+            queue_family_index,
+            target.image,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+    }
+
+    // Moves every user texture sampled this frame to SHADER_READ_ONLY_OPTIMAL, or back
+    // to its own layout when `restore` is set.
+    fn transition_user_textures(
+        &mut self,
+        command_buffer: CommandBuffer,
+        ids: &[TextureId],
+        target: &AllocatedImage,
+        restore: bool,
+    ) {
+        for id in ids {
+            let texture = &self.user_textures[id];
+            if texture.image.image == target.image {
+                if !restore {
+                    self.copy_to_mirror(command_buffer, *id, target);
+                }
+                continue;
+            }
+            if texture.layout == ImageLayout::SHADER_READ_ONLY_OPTIMAL {
+                continue;
+            }
+            let (old_layout, new_layout) = if restore {
+                (ImageLayout::SHADER_READ_ONLY_OPTIMAL, texture.layout)
+            } else {
+                (texture.layout, ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            };
+            image_transition(
+                &self.configuration.device,
+                command_buffer,
+                self.configuration.gfx_queue_family_index,
+                texture.image.image,
+                old_layout,
+                new_layout,
+            );
+        }
+    }
+
+    fn set_texture(
+        &mut self,
+        command_buffer: CommandBuffer,
+        frame_index: usize,
+        id: TextureId,
+        delta: &ImageDelta,
+    ) {
+        let pixels: Vec<u8> = match &delta.image {
+            ImageData::Color(image) => image.pixels.iter().flat_map(|c| c.to_array()).collect(),
+            ImageData::Font(image) => image
+                .srgba_pixels(None)
+                .flat_map(|c| c.to_array())
+                .collect(),
+        };
+        let [width, height] = delta.image.size();
+        let (image, offset, old_layout) = match delta.pos {
+            Some([x, y]) => {
+                let Some(texture) = self.textures.get(&id) else {
+                    warn!("Partial update for unknown egui texture {id:?}");
+                    return;
+                };
+                (
+                    texture.image.image,
+                    Offset3D::default().x(x as i32).y(y as i32),
+                    ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                )
+            }
+            None => {
+                let texture = self.create_texture(width as u32, height as u32, delta.options);
+                self.debug_utils
+                    .name(texture.image.image, &format!("egui texture {id:?}"));
+                self.debug_utils
+                    .name(texture.image.image_view, &format!("egui texture {id:?} view"));
+                self.debug_utils
+                    .name(texture.descriptor_set, &format!("egui texture {id:?}"));
+                let image = texture.image.image;
+                if let Some(old_texture) = self.textures.insert(id, texture) {
+                    self.frames[frame_index].retired_textures.push(old_texture);
+                }
+                (image, Offset3D::default(), ImageLayout::UNDEFINED)
+            }
+        };
+
+        let mut staging_buffer =
+            create_staging_buffer(&self.allocator, pixels.len() as u64).unwrap();
+        self.debug_utils
+            .name(staging_buffer.buffer, &format!("egui staging buffer {id:?}"));
+        staging_buffer.write(0, &pixels);
+
+        let device = &self.configuration.device;
+        let queue_family_index = self.configuration.gfx_queue_family_index;
+        image_transition(
+            device,
+            command_buffer,
+            queue_family_index,
+            image,
+            old_layout,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+        );
+        let region = BufferImageCopy::default()
+            .image_subresource(
+                ImageSubresourceLayers::default()
+                    .aspect_mask(ImageAspectFlags::COLOR)
+                    .mip_level(0)
+                    .base_array_layer(0)
+                    .layer_count(1),
+            )
+            .image_offset(offset)
+            .image_extent(
+                Extent3D::default()
+                    .width(width as u32)
+                    .height(height as u32)
+                    .depth(1),
+            );
+        unsafe {
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging_buffer.buffer,
+                image,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+            )
+        };
+        image_transition(
+            device,
+            command_buffer,
+            queue_family_index,
+            image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        self.frames[frame_index].staging_buffers.push(staging_buffer);
+    }
+
+    fn collect_garbage(&mut self, frame_index: usize) {
+        let frame = &mut self.frames[frame_index];
+        frame.staging_buffers.clear();
+        let retired_textures = std::mem::take(&mut frame.retired_textures);
+        let retired_user_textures = std::mem::take(&mut frame.retired_user_textures);
+        for texture in retired_textures {
+            self.destroy_texture(texture);
+        }
+        for texture in retired_user_textures {
+            self.destroy_user_texture(texture);
+        }
+    }
+
+    // Copies every mesh into this frame's ring, vertices and indices back to back
+    fn upload_meshes(&mut self, frame_index: usize, ring: &RingBuffer) -> bool {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        for primitive in &self.render_information.primitives {
+            if let Primitive::Mesh(mesh) = &primitive.primitive {
+                vertices.extend_from_slice(&mesh.vertices);
+                indices.extend_from_slice(&mesh.indices);
+            }
+        }
+        let frame = &mut self.frames[frame_index];
+        if indices.is_empty() {
+            frame.vertices = None;
+            frame.indices = None;
+            return false;
+        }
+        frame.vertices = Some(ring.push(&vertices));
+        frame.indices = Some(ring.push(&indices));
+        true
+    }
+
+    fn ensure_framebuffer(&mut self, allocated_image: &AllocatedImage) -> Framebuffer {
+        let device = &self.configuration.device;
+        if let Some((image_view, framebuffer)) = &self.framebuffer {
+            if *image_view == allocated_image.image_view {
+                return **framebuffer;
+            }
+        }
+        let image_views = [allocated_image.image_view];
+        let framebuffer_create_info = FramebufferCreateInfo::default()
+            .width(allocated_image.extent.width)
+            .height(allocated_image.extent.height)
+            .render_pass(*self.configuration.render_pass)
+            .attachments(&image_views)
+            .layers(1);
+        let framebuffer = unsafe {
+            device
                 .create_framebuffer(&framebuffer_create_info, None)
-                .unwrap();
-            let render_pass_info = RenderPassBeginInfo::default()
-                .render_area(Rect2D {
-                    offset: Offset2D::default(),
-                    extent: self.render_information.extent,
-                })
-                .render_pass(self.configuration.render_pass)
-                .framebuffer(framebuffer)
-                .clear_values(&[ClearValue {
-                    color: ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 1.0],
-                    },
-                }]);
-            
+                .unwrap()
+        };
+        self.framebuffer = Some((
+            allocated_image.image_view,
+            Owned::new(device.clone(), framebuffer),
+        ));
+        self.debug_utils.name(framebuffer, "egui framebuffer");
+        framebuffer
+    }
+
+    fn begin_render_pass(
+        &mut self,
+        command_buffer: CommandBuffer,
+        allocated_image: &AllocatedImage,
+        extent: Extent2D,
+    ) {
+        let framebuffer = self.ensure_framebuffer(allocated_image);
+        let render_pass_info = RenderPassBeginInfo::default()
+            .render_area(Rect2D {
+                offset: Offset2D::default(),
+                extent,
+            })
+            .render_pass(*self.configuration.render_pass)
+            .framebuffer(framebuffer);
+        unsafe {
             self.configuration.device.cmd_begin_render_pass(
-                self.command_buffer,
+                command_buffer,
                 &render_pass_info,
-                SubpassContents::default(),
+                SubpassContents::INLINE,
             );
         };
     }
 
-    fn end_render_pass(&self) {
+    fn end_render_pass(&self, command_buffer: CommandBuffer) {
         unsafe {
             self.configuration
                 .device
-                .cmd_end_render_pass(self.command_buffer)
+                .cmd_end_render_pass(command_buffer)
         };
     }
 
-}
+    fn scissor(&self, clip_rect: Rect, extent: Extent2D) -> Option<Rect2D> {
+        let pixels_per_point = self.render_information.pixels_per_point;
+        let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, extent.width as f32);
+        let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, extent.height as f32);
+        let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(min_x, extent.width as f32);
+        let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(min_y, extent.height as f32);
+        if max_x <= min_x || max_y <= min_y {
+            return None;
+        }
+        Some(Rect2D {
+            offset: Offset2D::default().x(min_x as i32).y(min_y as i32),
+            extent: Extent2D::default()
+                .width((max_x - min_x) as u32)
+                .height((max_y - min_y) as u32),
+        })
+    }
 
-impl Renderer for EGUIRenderer {
-    fn draw(&self, allocated_image: Arc<AllocatedImage>) -> CommandBuffer {
-        let device = self.configuration.device.clone();
-        command_buffers::begin_command_buffer(
-            &device,
-            self.command_buffer,
-            CommandBufferUsageFlags::ONE_TIME_SUBMIT,
-        );
-        self.begin_render_pass(allocated_image);
-        //       unsafe { self.configuration.device.cmd_draw_indexed(self.command_buffer, index_count, instance_count, first_index, vertex_offset, first_instance) };
-        self.end_render_pass();
-        unsafe { device.end_command_buffer(self.command_buffer) };
-        self.command_buffer
+    fn descriptor_set(&self, id: TextureId) -> Option<DescriptorSet> {
+        self.textures
+            .get(&id)
+            .map(|texture| texture.descriptor_set)
+            .or_else(|| self.user_textures.get(&id).map(|texture| texture.descriptor_set))
     }
 
-    fn buffer_allocation(&mut self, allocator: Arc<Allocator>, extent: Extent2D) -> Allocation {
-        let context = &self.configuration.context;
+    // Binds the egui pipeline and its buffers, called again after every paint callback
+    // since the callback may have replaced any of them.
+    fn bind_egui_state(&self, command_buffer: CommandBuffer, frame_index: usize, extent: Extent2D) {
         let device = &self.configuration.device;
-        let raw_input = RawInput::default();
-        debug!("{:?}", raw_input);
-        context.begin_pass(raw_input);
-        egui::SidePanel::new(egui::panel::Side::Right, "Side Panel").show(
-            &self.configuration.context,
-            |ui| {
-                ui.label("Hello World!");
-                let hello_button = ui.button("Hellooo");
-                if hello_button.clicked() {}
+        let frame = &self.frames[frame_index];
+        let pixels_per_point = self.render_information.pixels_per_point;
+        let screen_size_in_points = [
+            extent.width as f32 / pixels_per_point,
+            extent.height as f32 / pixels_per_point,
+        ];
+        let viewport = Viewport::default()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, *self.pipeline);
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_push_constants(
+                command_buffer,
+                *self.pipeline_layout,
+                ShaderStageFlags::VERTEX,
+                0,
+                as_bytes(&screen_size_in_points),
+            );
+            if let (Some(vertices), Some(indices)) = (frame.vertices, frame.indices) {
+                device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[vertices.buffer],
+                    &[vertices.offset],
+                );
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    indices.buffer,
+                    indices.offset,
+                    IndexType::UINT32,
+                );
+            }
+        }
+    }
+
+    fn draw_callback(
+        &self,
+        command_buffer: CommandBuffer,
+        frame_index: usize,
+        extent: Extent2D,
+        clip_rect: Rect,
+        callback: &PaintCallback,
+    ) {
+        let Some(callback_fn) = callback.callback.downcast_ref::<CallbackFn>() else {
+            warn!("Skipping egui paint callback that is not a CallbackFn");
+            return;
+        };
+        let Some(scissor) = self.scissor(clip_rect, extent) else {
+            return;
+        };
+        let info = PaintCallbackInfo {
+            viewport: callback.rect,
+            clip_rect,
+            pixels_per_point: self.render_information.pixels_per_point,
+            screen_size_px: [extent.width, extent.height],
+        };
+        let viewport_in_pixels = info.viewport_in_pixels();
+        let viewport = Viewport::default()
+            .x(viewport_in_pixels.left_px as f32)
+            .y(viewport_in_pixels.top_px as f32)
+            .width(viewport_in_pixels.width_px as f32)
+            .height(viewport_in_pixels.height_px as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+        let device = &self.configuration.device;
+        unsafe {
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+        }
+        callback_fn.call(
+            info,
+            &CallbackContext {
+                device,
+                command_buffer,
+                render_pass: *self.configuration.render_pass,
+                frame_index,
             },
         );
-        let output = context.end_pass();
-        let clipped_primitives = self
-            .configuration
-            .context
-            .tessellate(output.shapes, context.pixels_per_point());
+        self.bind_egui_state(command_buffer, frame_index, extent);
+    }
+
+    fn draw_primitives(&self, command_buffer: CommandBuffer, frame_index: usize, extent: Extent2D) {
+        let device = &self.configuration.device;
+        self.bind_egui_state(command_buffer, frame_index, extent);
+
+        let mut vertex_offset = 0;
+        let mut first_index = 0;
         for ClippedPrimitive {
-            primitive,
             clip_rect,
-        } in clipped_primitives
+            primitive,
+        } in &self.render_information.primitives
         {
             match primitive {
-                egui::epaint::Primitive::Mesh(mesh) => {
-                    self.render_information = RenderInformation {
-                        vertices: mesh.vertices,
-                        indices: mesh.indices,
-                        extent,
-                    };
+                Primitive::Mesh(mesh) => {
+                    let descriptor_set = self.descriptor_set(mesh.texture_id);
+                    if let (Some(descriptor_set), Some(scissor)) =
+                        (descriptor_set, self.scissor(*clip_rect, extent))
+                    {
+                        unsafe {
+                            device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+                            device.cmd_bind_descriptor_sets(
+                                command_buffer,
+                                PipelineBindPoint::GRAPHICS,
+                                *self.pipeline_layout,
+                                0,
+                                &[descriptor_set],
+                                &[],
+                            );
+                            device.cmd_draw_indexed(
+                                command_buffer,
+                                mesh.indices.len() as u32,
+                                1,
+                                first_index,
+                                vertex_offset,
+                                0,
+                            );
+                        }
+                    }
+                    vertex_offset += mesh.vertices.len() as i32;
+                    first_index += mesh.indices.len() as u32;
+                }
+                Primitive::Callback(callback) => {
+                    self.draw_callback(command_buffer, frame_index, extent, *clip_rect, callback);
                 }
-                egui::epaint::Primitive::Callback(paint_callback) => todo!(),
             }
         }
+    }
+}
 
-        let queue_family_indices = [self.configuration.gfx_queue_family_index];
-        let vertex_buffer_create_info = BufferCreateInfo::default()
-            .queue_family_indices(&queue_family_indices)
-            .sharing_mode(SharingMode::EXCLUSIVE)
-            .usage(BufferUsageFlags::VERTEX_BUFFER)
-            .size(self.render_information.vertices.len() as u64);
-        let mut allocation_create_info = AllocationCreateInfo::default();
-        allocation_create_info.usage = MemoryUsage::Unknown;
-        allocation_create_info.memory_type_bits = MemoryPropertyFlags::HOST_VISIBLE.as_raw();
-        unsafe {
-            let (mut buffer, mut vertex_buffer_allocation) = allocator
-                .create_buffer(&vertex_buffer_create_info, &allocation_create_info)
-                .unwrap();
-            let data = allocator.map_memory(&mut vertex_buffer_allocation);
-            vertex_buffer_allocation
+impl Renderer for EGUIRenderer {
+    fn context(&self) -> &Context {
+        &self.configuration.context
+    }
+
+    fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> EventResponse {
+        self.configuration.egui_state.on_window_event(window, event)
+    }
+
+    fn prepare_frame(&mut self, window: &Window, ui: &mut dyn FnMut(&Context)) -> Duration {
+        let context = self.configuration.context.clone();
+        let raw_input = self.configuration.egui_state.take_egui_input(window);
+        context.begin_pass(raw_input);
+        ui(&context);
+        let output = context.end_pass();
+        self.configuration
+            .egui_state
+            .handle_platform_output(window, output.platform_output);
+        self.render_information
+            .textures_delta
+            .append(output.textures_delta);
+        self.render_information.primitives =
+            context.tessellate(output.shapes, output.pixels_per_point);
+        self.render_information.pixels_per_point = output.pixels_per_point;
+        output
+            .viewport_output
+            .get(&ViewportId::ROOT)
+            .map_or(Duration::MAX, |viewport| viewport.repaint_delay)
+    }
+
+    fn draw(
+        &mut self,
+        command_buffer: CommandBuffer,
+        frame_index: usize,
+        allocated_image: Arc<AllocatedImage>,
+        ring: &RingBuffer,
+    ) {
+        self.collect_garbage(frame_index);
+        let textures_delta = std::mem::take(&mut self.render_information.textures_delta);
+        for (id, delta) in &textures_delta.set {
+            self.set_texture(command_buffer, frame_index, *id, delta);
+        }
+
+        let has_callbacks = self
+            .render_information
+            .primitives
+            .iter()
+            .any(|primitive| matches!(primitive.primitive, Primitive::Callback(_)));
+        if self.upload_meshes(frame_index, ring) || has_callbacks {
+            let extent = Extent2D::default()
+                .width(allocated_image.extent.width)
+                .height(allocated_image.extent.height);
+            let user_textures = self.used_user_textures();
+            self.transition_user_textures(command_buffer, &user_textures, &allocated_image, false);
+            self.begin_render_pass(command_buffer, &allocated_image, extent);
+            self.draw_primitives(command_buffer, frame_index, extent);
+            self.end_render_pass(command_buffer);
+            self.transition_user_textures(command_buffer, &user_textures, &allocated_image, true);
+        }
+
+        for id in textures_delta.free {
+            if let Some(texture) = self.textures.remove(&id) {
+                self.frames[frame_index].retired_textures.push(texture);
+            }
+            if let Some(texture) = self.user_textures.remove(&id) {
+                self.frames[frame_index].retired_user_textures.push(texture);
+            }
         }
     }
 }
@@ -0,0 +1,166 @@
+use std::{ops::Deref, sync::Arc};
+
+use ash::{
+    ext::debug_utils,
+    khr::{surface, swapchain},
+    vk::{
+        CommandPool, DebugUtilsMessengerEXT, DescriptorPool, DescriptorSetLayout, Fence,
+        Framebuffer, Handle, ImageView, Pipeline, PipelineCache, PipelineLayout, RenderPass,
+        Sampler, Semaphore, ShaderModule, SurfaceKHR, SwapchainKHR,
+    },
+    Device, Instance,
+};
+
+use super::{
+    object_tracker::{report_leaks, track, untrack},
+    validation::ValidationSink,
+};
+
+pub trait DeviceHandle: Handle + Copy {
+    unsafe fn destroy(self, device: &Device);
+}
+
+macro_rules! device_handles {
+    ($($handle:ty => $destroy:ident),* $(,)?) => {
+        $(
+            impl DeviceHandle for $handle {
+                unsafe fn destroy(self, device: &Device) {
+                    unsafe { device.$destroy(self, None) }
+                }
+            }
+        )*
+    };
+}
+
+device_handles!(
+    CommandPool => destroy_command_pool,
+    DescriptorPool => destroy_descriptor_pool,
+    DescriptorSetLayout => destroy_descriptor_set_layout,
+    Fence => destroy_fence,
+    Framebuffer => destroy_framebuffer,
+    ImageView => destroy_image_view,
+    Pipeline => destroy_pipeline,
+    PipelineCache => destroy_pipeline_cache,
+    PipelineLayout => destroy_pipeline_layout,
+    RenderPass => destroy_render_pass,
+    Sampler => destroy_sampler,
+    Semaphore => destroy_semaphore,
+    ShaderModule => destroy_shader_module,
+);
+
+// Destroys the handle when dropped. The caller is responsible for the GPU being done
+// with it by then, either by waiting for the device or by retiring it with a frame.
+pub struct Owned<H: DeviceHandle> {
+    handle: H,
+    device: Arc<Device>,
+}
+
+impl<H: DeviceHandle> Owned<H> {
+    #[track_caller]
+    pub fn new(device: Arc<Device>, handle: H) -> Self {
+        track(handle);
+        Self { handle, device }
+    }
+}
+
+impl<H: DeviceHandle> Deref for Owned<H> {
+    type Target = H;
+
+    fn deref(&self) -> &H {
+        &self.handle
+    }
+}
+
+impl<H: DeviceHandle> Drop for Owned<H> {
+    fn drop(&mut self) {
+        untrack(self.handle);
+        unsafe { self.handle.destroy(&self.device) }
+    }
+}
+
+pub struct Swapchain {
+    handle: SwapchainKHR,
+    swapchain_device: swapchain::Device,
+}
+
+impl Swapchain {
+    #[track_caller]
+    pub fn new(swapchain_device: swapchain::Device, handle: SwapchainKHR) -> Self {
+        track(handle);
+        Self {
+            handle,
+            swapchain_device,
+        }
+    }
+}
+
+impl Deref for Swapchain {
+    type Target = SwapchainKHR;
+
+    fn deref(&self) -> &SwapchainKHR {
+        &self.handle
+    }
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        untrack(self.handle);
+        unsafe { self.swapchain_device.destroy_swapchain(self.handle, None) }
+    }
+}
+
+pub struct Surface {
+    handle: SurfaceKHR,
+    surface_instance: surface::Instance,
+}
+
+impl Surface {
+    #[track_caller]
+    pub fn new(surface_instance: surface::Instance, handle: SurfaceKHR) -> Self {
+        track(handle);
+        Self {
+            handle,
+            surface_instance,
+        }
+    }
+}
+
+impl Deref for Surface {
+    type Target = SurfaceKHR;
+
+    fn deref(&self) -> &SurfaceKHR {
+        &self.handle
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        untrack(self.handle);
+        unsafe { self.surface_instance.destroy_surface(self.handle, None) }
+    }
+}
+
+// The objects everything else is created from. Keep it as the last field of its owner
+// so it is dropped after all of them, it reports whatever is still alive at that point.
+pub struct DeviceContext {
+    pub instance: Instance,
+    pub device: Arc<Device>,
+    pub debug_instance: debug_utils::Instance,
+    pub debugger: DebugUtilsMessengerEXT,
+    // Both messengers call back into the sink until the instance is gone. Fields are
+    // dropped after `drop` runs, so this reference is released after destroy_instance.
+    #[allow(dead_code)]
+    pub validation_sink: Arc<ValidationSink>,
+}
+
+impl Drop for DeviceContext {
+    fn drop(&mut self) {
+        report_leaks();
+        unsafe {
+            self.device.destroy_device(None);
+            self.debug_instance
+                .destroy_debug_utils_messenger(self.debugger, None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
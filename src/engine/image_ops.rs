@@ -94,13 +94,13 @@ pub fn copy_image_to_image(
     dst_extent: Extent2D,
 ) {
     let src_offset_3d = [
-        Offset3D::default()
-            .x(src_extent.width as i32)
-            .y(src_extent.height as i32)
-            .z(0),
         Offset3D::default()
             .x(0)
             .y(0)
+            .z(0),
+        Offset3D::default()
+            .x(src_extent.width as i32)
+            .y(src_extent.height as i32)
             .z(1),
     ];
 
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use ash::{
+    vk::{
+        CommandBuffer, CommandBufferResetFlags, CommandBufferUsageFlags, CommandPool, Fence, Queue,
+        SubmitInfo,
+    },
+    Device,
+};
+
+use super::{
+    command_buffers::{allocate_command_buffer, begin_command_buffer, create_command_pool},
+    debug_utils::DebugUtils,
+    handles::Owned,
+    sync_objects::create_fence,
+};
+
+// Records and submits one-off work such as uploads outside the frame loop, blocking
+// until the GPU has finished it.
+pub struct ImmediateSubmit {
+    command_buffer: CommandBuffer,
+    fence: Owned<Fence>,
+    command_pool: Owned<CommandPool>,
+    queue: Arc<Queue>,
+    device: Arc<Device>,
+}
+
+impl ImmediateSubmit {
+    pub fn new(device: &Arc<Device>, queue: Arc<Queue>, queue_family_index: u32) -> Self {
+        let command_pool = create_command_pool(device, queue_family_index);
+        let command_buffer = allocate_command_buffer(device, *command_pool);
+        Self {
+            command_buffer,
+            fence: create_fence(device),
+            command_pool,
+            queue,
+            device: device.clone(),
+        }
+    }
+
+    pub fn submit<F: FnOnce(&Device, CommandBuffer)>(&self, record: F) {
+        let device = &self.device;
+        let fences = [*self.fence];
+        unsafe {
+            device.reset_fences(&fences).unwrap();
+            device
+                .reset_command_buffer(self.command_buffer, CommandBufferResetFlags::empty())
+                .unwrap();
+            begin_command_buffer(device, self.command_buffer, CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            record(device, self.command_buffer);
+            device.end_command_buffer(self.command_buffer).unwrap();
+
+            let command_buffers = [self.command_buffer];
+            let submit_info = SubmitInfo::default().command_buffers(&command_buffers);
+            device
+                .queue_submit(*self.queue, &[submit_info], *self.fence)
+                .unwrap();
+            device.wait_for_fences(&fences, true, u64::MAX).unwrap();
+        }
+    }
+
+    pub fn name_objects(&self, debug_utils: &DebugUtils) {
+        debug_utils.name(*self.command_pool, "immediate command pool");
+        debug_utils.name(self.command_buffer, "immediate command buffer");
+        debug_utils.name(*self.fence, "immediate fence");
+    }
+}
@@ -0,0 +1,226 @@
+use std::{collections::HashMap, mem::size_of};
+
+use ash::vk::DrawIndexedIndirectCommand;
+use glam::{Mat4, Vec4};
+
+use super::{
+    mesh::{Mesh, Submesh},
+    mesh_renderer::MeshDraw,
+    ring_buffer::{RingAllocation, RingBuffer},
+};
+
+pub const INDIRECT_COMMAND_SIZE: u32 = size_of::<DrawIndexedIndirectCommand>() as u32;
+
+// One entry of the instance buffer mesh_instanced.vert reads at set 1, binding 0. Laid
+// out for std430:
+//
+// struct Instance { mat4 model; vec4 color; };
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InstanceData {
+    pub model: Mat4,
+    pub color: Vec4,
+}
+
+// All instances of one mesh. Its commands are contiguous in the command buffer, one
+// per submesh in the mesh's own order. Batching is per mesh only: the renderer binds no
+// per-material state, so submeshes are not regrouped by material within or across meshes.
+pub struct IndirectBatch<'a, M = Mesh> {
+    pub mesh: &'a M,
+    pub first_command: u32,
+    pub command_count: u32,
+    pub first_instance: u32,
+    pub instance_count: u32,
+}
+
+// Not derived, that would require `M: Copy`
+impl<M> Clone for IndirectBatch<'_, M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M> Copy for IndirectBatch<'_, M> {}
+
+impl IndirectBatch<'_> {
+    // The batch's commands, each drawing `instance_count` instances.
+    pub fn commands(&self, instance_count: u32) -> Vec<DrawIndexedIndirectCommand> {
+        submesh_commands(&self.mesh.submeshes, self.first_instance, instance_count)
+    }
+}
+
+fn submesh_commands(
+    submeshes: &[Submesh],
+    first_instance: u32,
+    instance_count: u32,
+) -> Vec<DrawIndexedIndirectCommand> {
+    submeshes
+        .iter()
+        .map(|submesh| DrawIndexedIndirectCommand {
+            index_count: submesh.index_count,
+            instance_count,
+            first_index: submesh.first_index,
+            vertex_offset: 0,
+            first_instance,
+        })
+        .collect()
+}
+
+// Groups `draws` by mesh, in the order each mesh first appears, and lays out their
+// instances and commands. Generic over the mesh so it can run without a device.
+fn layout<'a, M>(
+    draws: impl IntoIterator<Item = (&'a M, InstanceData)>,
+    submeshes: impl Fn(&M) -> &[Submesh],
+) -> (
+    Vec<IndirectBatch<'a, M>>,
+    Vec<InstanceData>,
+    Vec<DrawIndexedIndirectCommand>,
+) {
+    let mut batch_indices: HashMap<*const M, usize> = HashMap::new();
+    let mut grouped: Vec<(&'a M, Vec<InstanceData>)> = Vec::new();
+    for (mesh, instance) in draws {
+        let index = *batch_indices.entry(mesh as *const M).or_insert_with(|| {
+            grouped.push((mesh, Vec::new()));
+            grouped.len() - 1
+        });
+        grouped[index].1.push(instance);
+    }
+
+    let mut batches = Vec::with_capacity(grouped.len());
+    let mut instances = Vec::new();
+    let mut commands = Vec::new();
+    for (mesh, mesh_instances) in grouped {
+        let batch = IndirectBatch {
+            mesh,
+            first_command: commands.len() as u32,
+            command_count: submeshes(mesh).len() as u32,
+            first_instance: instances.len() as u32,
+            instance_count: mesh_instances.len() as u32,
+        };
+        commands.extend(submesh_commands(
+            submeshes(mesh),
+            batch.first_instance,
+            batch.instance_count,
+        ));
+        instances.extend(mesh_instances);
+        batches.push(batch);
+    }
+    (batches, instances, commands)
+}
+
+// A frame's mesh draws flattened into an instance buffer and a buffer of
+// VkDrawIndexedIndirectCommand, both in the frame's ring. Both allocations are storage
+// buffers too, so a compute pass can rewrite instances or commands before
+// `MeshRenderer::draw_indirect` consumes them.
+pub struct IndirectDraws<'a> {
+    pub batches: Vec<IndirectBatch<'a>>,
+    pub instances: RingAllocation,
+    pub commands: RingAllocation,
+    pub instance_count: u32,
+    pub command_count: u32,
+}
+
+impl<'a> IndirectDraws<'a> {
+    // Groups `draws` by mesh, in the order each mesh first appears.
+    pub fn build(ring: &RingBuffer, draws: &[MeshDraw<'a>]) -> Self {
+        let (batches, instances, commands) = layout(
+            draws.iter().map(|draw| {
+                let instance = InstanceData {
+                    model: draw.transform,
+                    color: draw.color,
+                };
+                (draw.mesh, instance)
+            }),
+            |mesh| &mesh.submeshes,
+        );
+        Self {
+            batches,
+            instances: ring.push(&instances),
+            commands: ring.push(&commands),
+            instance_count: instances.len() as u32,
+            command_count: commands.len() as u32,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.command_count == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submesh(first_index: u32, index_count: u32, material: Option<usize>) -> Submesh {
+        Submesh {
+            name: String::new(),
+            first_index,
+            index_count,
+            material,
+        }
+    }
+
+    fn instance(id: f32) -> InstanceData {
+        InstanceData {
+            model: Mat4::from_translation(glam::Vec3::splat(id)),
+            color: Vec4::splat(id),
+        }
+    }
+
+    #[test]
+    fn groups_interleaved_draws_by_mesh() {
+        let cube = vec![submesh(0, 36, None)];
+        let robot = vec![
+            submesh(0, 30, Some(2)),
+            submesh(30, 60, Some(0)),
+            submesh(90, 12, Some(1)),
+        ];
+        let draws = [
+            (&robot, instance(0.0)),
+            (&cube, instance(1.0)),
+            (&robot, instance(2.0)),
+            (&cube, instance(3.0)),
+            (&robot, instance(4.0)),
+        ];
+        let (batches, instances, commands) = layout(draws, |mesh| mesh.as_slice());
+
+        // The robot appears first, so its batch comes first
+        assert_eq!(batches.len(), 2);
+        assert!(std::ptr::eq(batches[0].mesh, &robot));
+        assert!(std::ptr::eq(batches[1].mesh, &cube));
+        let ranges: Vec<_> = batches
+            .iter()
+            .map(|batch| {
+                (
+                    batch.first_command,
+                    batch.command_count,
+                    batch.first_instance,
+                    batch.instance_count,
+                )
+            })
+            .collect();
+        assert_eq!(ranges, [(0, 3, 0, 3), (3, 1, 3, 2)]);
+        assert_eq!(
+            instances,
+            [0.0, 2.0, 4.0, 1.0, 3.0].map(instance),
+            "instances are not grouped in draw order"
+        );
+
+        // The robot's submeshes in their own order whatever their materials, then the cube's
+        let drawn: Vec<_> = commands
+            .iter()
+            .map(|command| {
+                (
+                    command.first_index,
+                    command.index_count,
+                    command.first_instance,
+                    command.instance_count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            drawn,
+            [(0, 30, 0, 3), (30, 60, 0, 3), (90, 12, 0, 3), (0, 36, 3, 2)]
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{ElementState, MouseScrollDelta, WindowEvent},
+    keyboard::PhysicalKey,
+};
+
+pub const INPUT_BINDINGS_ENV: &str = "WURMPLE_INPUT_BINDINGS";
+// Trackpads report scrolling in pixels, roughly this many make up one wheel notch.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+// A physical button. Keys use winit's `KeyCode` names ("KeyW", "Space", "ArrowUp"),
+// mouse buttons its `MouseButton` names ("Left", "Right", "Middle").
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(String),
+    Mouse(String),
+}
+
+impl Binding {
+    pub fn key(name: &str) -> Self {
+        Self::Key(name.to_string())
+    }
+
+    pub fn mouse(name: &str) -> Self {
+        Self::Mouse(name.to_string())
+    }
+
+    fn is_pointer(&self) -> bool {
+        matches!(self, Self::Mouse(_))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisSource {
+    // -1 while `negative` is held, +1 while `positive` is, 0 for both or neither
+    Keys { negative: Binding, positive: Binding },
+    // Cursor movement this frame in physical pixels
+    CursorX,
+    CursorY,
+    // Scrolling this frame in wheel notches
+    WheelX,
+    WheelY,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AxisBinding {
+    pub source: AxisSource,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+impl AxisBinding {
+    pub fn new(source: AxisSource) -> Self {
+        Self { source, scale: 1.0 }
+    }
+
+    pub fn keys(negative: Binding, positive: Binding) -> Self {
+        Self::new(AxisSource::Keys { negative, positive })
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+// Named actions and axes and what drives them. Stored as JSON, e.g.
+// {"actions": {"jump": [{"Key": "Space"}]},
+//  "axes": {"move_x": [{"source": {"Keys": {"negative": {"Key": "KeyA"}, "positive": {"Key": "KeyD"}}}}]}}
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    #[serde(default)]
+    pub actions: HashMap<String, Vec<Binding>>,
+    #[serde(default)]
+    pub axes: HashMap<String, Vec<AxisBinding>>,
+}
+
+impl InputBindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_action(mut self, action: &str, binding: Binding) -> Self {
+        self.bind_action(action, binding);
+        self
+    }
+
+    pub fn with_axis(mut self, axis: &str, binding: AxisBinding) -> Self {
+        self.bind_axis(axis, binding);
+        self
+    }
+
+    // Adds another binding, every binding of an action triggers it.
+    pub fn bind_action(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    // Axes add up the value of every binding.
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        self.axes.entry(axis.to_string()).or_default().push(binding);
+    }
+
+    // Replaces whatever `action` was bound to.
+    pub fn rebind_action(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.actions.insert(action.to_string(), bindings);
+    }
+
+    pub fn rebind_axis(&mut self, axis: &str, bindings: Vec<AxisBinding>) {
+        self.axes.insert(axis.to_string(), bindings);
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        fs::write(path, json)
+    }
+
+    // Bindings from the file WURMPLE_INPUT_BINDINGS points at, if it is set and loads.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var_os(INPUT_BINDINGS_ENV)?;
+        Self::load(Path::new(&path))
+            .inspect_err(|err| warn!("Failed to load input bindings from {path:?}: {err}"))
+            .ok()
+    }
+}
+
+// Turns window events into per-frame action and axis state. Edges (`pressed`,
+// `released`) and deltas are collected between two calls to `end_frame`, so query them
+// from `Application::update`; fixed updates may run zero or several times a frame.
+#[derive(Default)]
+pub struct InputState {
+    bindings: InputBindings,
+    held: HashSet<Binding>,
+    pressed: HashSet<Binding>,
+    released: HashSet<Binding>,
+    cursor: Option<PhysicalPosition<f64>>,
+    cursor_delta: (f32, f32),
+    wheel: (f32, f32),
+}
+
+impl InputState {
+    pub fn new(bindings: InputBindings) -> Self {
+        Self {
+            bindings,
+            ..Default::default()
+        }
+    }
+
+    pub fn bindings(&self) -> &InputBindings {
+        &self.bindings
+    }
+
+    pub fn bindings_mut(&mut self) -> &mut InputBindings {
+        &mut self.bindings
+    }
+
+    pub fn set_bindings(&mut self, bindings: InputBindings) {
+        self.bindings = bindings;
+    }
+
+    // Presses, scrolling and movement egui wants for itself never reach the scene.
+    // Releases always do, so nothing stays stuck down when focus moves to the UI.
+    pub fn on_window_event(&mut self, event: &WindowEvent, ui_keyboard: bool, ui_pointer: bool) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let PhysicalKey::Code(code) = event.physical_key {
+                    let binding = Binding::Key(format!("{code:?}"));
+                    self.on_button(binding, event.state, ui_keyboard, event.repeat);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.on_button(Binding::Mouse(format!("{button:?}")), *state, ui_pointer, false);
+            }
+            WindowEvent::MouseWheel { delta, .. } if !ui_pointer => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (*x, *y),
+                    MouseScrollDelta::PixelDelta(position) => (
+                        position.x as f32 / PIXELS_PER_LINE,
+                        position.y as f32 / PIXELS_PER_LINE,
+                    ),
+                };
+                self.wheel.0 += x;
+                self.wheel.1 += y;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (Some(last), false) = (self.cursor, ui_pointer) {
+                    self.cursor_delta.0 += (position.x - last.x) as f32;
+                    self.cursor_delta.1 += (position.y - last.y) as f32;
+                }
+                self.cursor = Some(*position);
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::Focused(false) => {
+                self.released.extend(self.held.drain());
+            }
+            _ => {}
+        }
+    }
+
+    fn on_button(&mut self, binding: Binding, state: ElementState, captured: bool, repeat: bool) {
+        match state {
+            ElementState::Pressed if !captured && !repeat && self.held.insert(binding.clone()) => {
+                self.pressed.insert(binding);
+            }
+            ElementState::Released if self.held.remove(&binding) => {
+                self.released.insert(binding);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.cursor_delta = (0.0, 0.0);
+        self.wheel = (0.0, 0.0);
+    }
+
+    fn any(&self, action: &str, set: &HashSet<Binding>) -> bool {
+        self.bindings
+            .actions
+            .get(action)
+            .is_some_and(|bindings| bindings.iter().any(|binding| set.contains(binding)))
+    }
+
+    // Went down this frame.
+    pub fn pressed(&self, action: &str) -> bool {
+        self.any(action, &self.pressed)
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.any(action, &self.held)
+    }
+
+    // Went up this frame.
+    pub fn released(&self, action: &str) -> bool {
+        self.any(action, &self.released)
+    }
+
+    pub fn axis(&self, axis: &str) -> f32 {
+        let Some(bindings) = self.bindings.axes.get(axis) else {
+            return 0.0;
+        };
+        bindings
+            .iter()
+            .map(|binding| {
+                let value = match &binding.source {
+                    AxisSource::Keys { negative, positive } => {
+                        self.held.contains(positive) as i32 as f32
+                            - self.held.contains(negative) as i32 as f32
+                    }
+                    AxisSource::CursorX => self.cursor_delta.0,
+                    AxisSource::CursorY => self.cursor_delta.1,
+                    AxisSource::WheelX => self.wheel.0,
+                    AxisSource::WheelY => self.wheel.1,
+                };
+                value * binding.scale
+            })
+            .sum()
+    }
+
+    // Raw state for things that don't fit an action, like picking under the cursor.
+    pub fn is_down(&self, binding: &Binding) -> bool {
+        self.held.contains(binding)
+    }
+
+    pub fn cursor_position(&self) -> Option<(f32, f32)> {
+        self.cursor
+            .map(|position| (position.x as f32, position.y as f32))
+    }
+
+    // Only counts buttons the scene has seen, not ones egui kept for itself.
+    pub fn pointer_held(&self) -> bool {
+        self.held.iter().any(Binding::is_pointer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{DeviceId, MouseButton, TouchPhase};
+
+    use super::*;
+
+    fn bindings() -> InputBindings {
+        InputBindings::new()
+            .with_action("jump", Binding::key("Space"))
+            .with_action("jump", Binding::mouse("Left"))
+            .with_axis("move_x", AxisBinding::keys(Binding::key("KeyA"), Binding::key("KeyD")))
+            .with_axis("look_x", AxisBinding::new(AxisSource::CursorX).with_scale(0.5))
+            .with_axis("zoom", AxisBinding::new(AxisSource::WheelY))
+    }
+
+    // Keyboard events can't be built outside winit, keys go in one step further down.
+    fn key(input: &mut InputState, name: &str, state: ElementState) {
+        input.on_button(Binding::key(name), state, false, false);
+    }
+
+    fn cursor_moved(x: f64, y: f64) -> WindowEvent {
+        WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: PhysicalPosition::new(x, y),
+        }
+    }
+
+    #[test]
+    fn edges_last_one_frame() {
+        let mut input = InputState::new(bindings());
+        key(&mut input, "Space", ElementState::Pressed);
+        assert!(input.pressed("jump") && input.held("jump") && !input.released("jump"));
+
+        input.end_frame();
+        assert!(!input.pressed("jump") && input.held("jump"));
+
+        key(&mut input, "Space", ElementState::Released);
+        assert!(input.released("jump") && !input.held("jump"));
+
+        input.end_frame();
+        assert!(!input.pressed("jump") && !input.held("jump") && !input.released("jump"));
+    }
+
+    #[test]
+    fn mouse_buttons_trigger_actions() {
+        let mut input = InputState::new(bindings());
+        let click = |state| WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button: MouseButton::Left,
+        };
+        input.on_window_event(&click(ElementState::Pressed), false, false);
+        assert!(input.pressed("jump") && input.pointer_held());
+        input.end_frame();
+        input.on_window_event(&click(ElementState::Released), false, false);
+        assert!(input.released("jump") && !input.pointer_held());
+    }
+
+    #[test]
+    fn repeats_and_captured_presses_are_ignored() {
+        let mut input = InputState::new(bindings());
+        input.on_button(Binding::key("Space"), ElementState::Pressed, true, false);
+        assert!(!input.pressed("jump") && !input.held("jump"));
+        key(&mut input, "Space", ElementState::Released);
+        assert!(!input.released("jump"));
+
+        key(&mut input, "Space", ElementState::Pressed);
+        input.end_frame();
+        input.on_button(Binding::key("Space"), ElementState::Pressed, false, true);
+        assert!(!input.pressed("jump") && input.held("jump"));
+    }
+
+    #[test]
+    fn focus_loss_releases_everything() {
+        let mut input = InputState::new(bindings());
+        key(&mut input, "Space", ElementState::Pressed);
+        input.end_frame();
+        input.on_window_event(&WindowEvent::Focused(false), false, false);
+        assert!(input.released("jump") && !input.held("jump"));
+    }
+
+    #[test]
+    fn key_axes_follow_held_keys() {
+        let mut input = InputState::new(bindings());
+        key(&mut input, "KeyA", ElementState::Pressed);
+        assert_eq!(input.axis("move_x"), -1.0);
+        input.end_frame();
+        // Held keys keep driving the axis on later frames
+        assert_eq!(input.axis("move_x"), -1.0);
+        key(&mut input, "KeyD", ElementState::Pressed);
+        assert_eq!(input.axis("move_x"), 0.0);
+        key(&mut input, "KeyA", ElementState::Released);
+        assert_eq!(input.axis("move_x"), 1.0);
+        assert_eq!(input.axis("unbound"), 0.0);
+    }
+
+    #[test]
+    fn cursor_and_wheel_axes_reset_each_frame() {
+        let mut input = InputState::new(bindings());
+        input.on_window_event(&cursor_moved(10.0, 10.0), false, false);
+        input.on_window_event(&cursor_moved(30.0, 15.0), false, false);
+        input.on_window_event(
+            &WindowEvent::MouseWheel {
+                device_id: DeviceId::dummy(),
+                delta: MouseScrollDelta::LineDelta(0.0, 1.0),
+                phase: TouchPhase::Moved,
+            },
+            false,
+            false,
+        );
+        input.on_window_event(
+            &WindowEvent::MouseWheel {
+                device_id: DeviceId::dummy(),
+                delta: MouseScrollDelta::PixelDelta(PhysicalPosition::new(0.0, 40.0)),
+                phase: TouchPhase::Moved,
+            },
+            false,
+            false,
+        );
+        assert_eq!(input.axis("look_x"), 10.0);
+        assert_eq!(input.axis("zoom"), 3.0);
+
+        input.end_frame();
+        assert_eq!(input.axis("look_x"), 0.0);
+        assert_eq!(input.axis("zoom"), 0.0);
+
+        // Movement over the UI only moves the cursor
+        input.on_window_event(&cursor_moved(50.0, 15.0), false, true);
+        assert_eq!(input.axis("look_x"), 0.0);
+        assert_eq!(input.cursor_position(), Some((50.0, 15.0)));
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    env, fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use ash::vk::MemoryHeapFlags;
+use log::{debug, warn};
+use serde::Serialize;
+use vk_mem::{Allocation, Allocator};
+
+pub const MEMORY_REPORT_ENV: &str = "WURMPLE_MEMORY_REPORT";
+const BUDGET_WARNING_RATIO: f64 = 0.9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum AllocationCategory {
+    Image,
+    Buffer,
+    Staging,
+}
+
+impl AllocationCategory {
+    pub const ALL: [AllocationCategory; 3] = [Self::Image, Self::Buffer, Self::Staging];
+}
+
+#[derive(Default, Clone, Copy, Debug, Serialize)]
+pub struct CategoryStats {
+    pub live_allocations: u64,
+    pub live_bytes: u64,
+    pub peak_bytes: u64,
+    pub total_allocations: u64,
+}
+
+struct Counter {
+    live_allocations: AtomicU64,
+    live_bytes: AtomicU64,
+    peak_bytes: AtomicU64,
+    total_allocations: AtomicU64,
+}
+
+impl Counter {
+    const fn new() -> Self {
+        Self {
+            live_allocations: AtomicU64::new(0),
+            live_bytes: AtomicU64::new(0),
+            peak_bytes: AtomicU64::new(0),
+            total_allocations: AtomicU64::new(0),
+        }
+    }
+}
+
+// Allocations happen all over the engine with only an `&Allocator` at hand, so the
+// per category counters are process wide instead of threaded through every call.
+static COUNTERS: [Counter; 3] = [Counter::new(), Counter::new(), Counter::new()];
+
+fn counter(category: AllocationCategory) -> &'static Counter {
+    &COUNTERS[category as usize]
+}
+
+fn allocation_size(allocator: &Allocator, allocation: &Allocation) -> u64 {
+    allocator.get_allocation_info(allocation).size
+}
+
+pub fn track_allocation(allocator: &Allocator, allocation: &Allocation, category: AllocationCategory) {
+    let size = allocation_size(allocator, allocation);
+    let counter = counter(category);
+    counter.live_allocations.fetch_add(1, Ordering::Relaxed);
+    counter.total_allocations.fetch_add(1, Ordering::Relaxed);
+    let live_bytes = counter.live_bytes.fetch_add(size, Ordering::Relaxed) + size;
+    counter.peak_bytes.fetch_max(live_bytes, Ordering::Relaxed);
+}
+
+pub fn track_free(allocator: &Allocator, allocation: &Allocation, category: AllocationCategory) {
+    let size = allocation_size(allocator, allocation);
+    let counter = counter(category);
+    counter.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    counter.live_bytes.fetch_sub(size, Ordering::Relaxed);
+}
+
+pub fn category_stats(category: AllocationCategory) -> CategoryStats {
+    let counter = counter(category);
+    CategoryStats {
+        live_allocations: counter.live_allocations.load(Ordering::Relaxed),
+        live_bytes: counter.live_bytes.load(Ordering::Relaxed),
+        peak_bytes: counter.peak_bytes.load(Ordering::Relaxed),
+        total_allocations: counter.total_allocations.load(Ordering::Relaxed),
+    }
+}
+
+pub fn default_report_path() -> PathBuf {
+    env::var_os(MEMORY_REPORT_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join("wurmple_memory_report.json"))
+}
+
+#[derive(Default, Clone, Copy, Debug, Serialize)]
+pub struct HeapInfo {
+    pub device_local: bool,
+    pub size: u64,
+    pub budget: u64,
+    pub usage: u64,
+    pub block_count: u32,
+    pub block_bytes: u64,
+    pub allocation_count: u32,
+    pub allocation_bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct MemoryReport {
+    pub heaps: Vec<HeapInfo>,
+    pub categories: Vec<(AllocationCategory, CategoryStats)>,
+}
+
+#[derive(Default)]
+pub struct MemoryStats {
+    pub heaps: Vec<HeapInfo>,
+    near_budget: Vec<bool>,
+}
+
+impl MemoryStats {
+    pub fn update(&mut self, allocator: &Allocator) {
+        let memory_properties = unsafe { allocator.get_memory_properties() };
+        let Ok(budgets) = allocator.get_heap_budgets() else {
+            return;
+        };
+        self.heaps = budgets
+            .iter()
+            .zip(memory_properties.memory_heaps.iter())
+            .map(|(budget, heap)| HeapInfo {
+                device_local: heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL),
+                size: heap.size,
+                budget: budget.budget,
+                usage: budget.usage,
+                block_count: budget.statistics.blockCount,
+                block_bytes: budget.statistics.blockBytes,
+                allocation_count: budget.statistics.allocationCount,
+                allocation_bytes: budget.statistics.allocationBytes,
+            })
+            .collect();
+        self.near_budget.resize(self.heaps.len(), false);
+
+        // Only warn when a heap crosses the threshold, not on every frame it stays above it.
+        for (idx, heap) in self.heaps.iter().enumerate() {
+            let near_budget = heap.usage as f64 >= heap.budget as f64 * BUDGET_WARNING_RATIO;
+            if near_budget && !self.near_budget[idx] {
+                warn!(
+                    "Memory heap {idx} is at {} of its {} byte budget",
+                    heap.usage, heap.budget
+                );
+            }
+            self.near_budget[idx] = near_budget;
+        }
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        MemoryReport {
+            heaps: self.heaps.clone(),
+            categories: AllocationCategory::ALL
+                .iter()
+                .map(|category| (*category, category_stats(*category)))
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(&self.report())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn write_report(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.to_json()?)?;
+        debug!("Wrote memory report to {path:?}");
+        Ok(())
+    }
+}
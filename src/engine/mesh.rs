@@ -0,0 +1,466 @@
+use std::{
+    io::{Error, ErrorKind},
+    mem::size_of_val,
+    path::Path,
+    slice,
+    sync::Arc,
+};
+
+use ash::vk::{BufferCopy, BufferUsageFlags, DeviceSize};
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use log::{debug, warn};
+use vk_mem::{AllocationCreateFlags, Allocator, MemoryUsage};
+
+use super::{
+    allocated_buffer::{create_allocated_buffer, create_staging_buffer, AllocatedBuffer},
+    debug_utils::DebugUtils,
+    immediate_submit::ImmediateSubmit,
+    memory_stats::AllocationCategory,
+};
+
+// Interleaved vertex layout shared by every mesh, 48 bytes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    // xyz along +u, w the handedness of the bitangent
+    pub tangent: [f32; 4],
+}
+
+// A range of the index buffer drawn with one material, one per OBJ object or glTF
+// primitive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Submesh {
+    pub name: String,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub material: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+}
+
+// Geometry on the CPU. Indices are absolute into `vertices`, every submesh shares the
+// one vertex range.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub submeshes: Vec<Submesh>,
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(err: E) -> Error {
+    Error::new(ErrorKind::InvalidData, err)
+}
+
+fn in_file(path: &Path, err: Error) -> Error {
+    Error::new(err.kind(), format!("{}: {err}", path.display()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn generate_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+        let p = [a, b, c].map(|index| Vec3::from(vertices[index].position));
+        // Not normalized, so larger faces weigh more
+        let normal = (p[1] - p[0]).cross(p[2] - p[0]);
+        for index in [a, b, c] {
+            normals[index] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal.normalize_or(Vec3::Y).to_array();
+    }
+}
+
+// Per-triangle tangents from the UV derivatives, averaged per vertex and made
+// orthogonal to the normal.
+fn generate_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+        let p = [a, b, c].map(|index| Vec3::from(vertices[index].position));
+        let uv = [a, b, c].map(|index| Vec2::from(vertices[index].uv));
+        let (edge1, edge2) = (p[1] - p[0], p[2] - p[0]);
+        let (delta1, delta2) = (uv[1] - uv[0], uv[2] - uv[0]);
+        let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * delta2.y - edge2 * delta1.y) / determinant;
+        let bitangent = (edge2 * delta1.x - edge1 * delta2.x) / determinant;
+        for index in [a, b, c] {
+            tangents[index] += tangent;
+            bitangents[index] += bitangent;
+        }
+    }
+    for ((vertex, tangent), bitangent) in vertices.iter_mut().zip(tangents).zip(bitangents) {
+        let normal = Vec3::from(vertex.normal);
+        let tangent = (tangent - normal * normal.dot(tangent))
+            .try_normalize()
+            .unwrap_or_else(|| normal.any_orthonormal_vector());
+        let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = tangent.extend(handedness).to_array();
+    }
+}
+
+impl MeshData {
+    // Picks the loader from the extension: .obj, .gltf or .glb.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("obj") => Self::load_obj(path),
+            Some("gltf" | "glb") => Self::load_gltf(path),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("no mesh loader for {}", path.display()),
+            )),
+        }
+    }
+
+    pub fn load_obj(path: &Path) -> Result<Self, Error> {
+        let (models, _) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(invalid_data)?;
+        let mut data = Self {
+            name: file_name(path),
+            ..Default::default()
+        };
+        for model in models {
+            let mesh = model.mesh;
+            let has_normals = !mesh.normals.is_empty();
+            let vertices = mesh
+                .positions
+                .chunks_exact(3)
+                .enumerate()
+                .map(|(i, position)| Vertex {
+                    position: [position[0], position[1], position[2]],
+                    normal: mesh
+                        .normals
+                        .get(i * 3..i * 3 + 3)
+                        .map_or([0.0; 3], |normal| [normal[0], normal[1], normal[2]]),
+                    // OBJ puts v = 0 at the bottom of the texture
+                    uv: mesh
+                        .texcoords
+                        .get(i * 2..i * 2 + 2)
+                        .map_or([0.0; 2], |uv| [uv[0], 1.0 - uv[1]]),
+                    tangent: [0.0; 4],
+                })
+                .collect();
+            data.append(model.name, vertices, mesh.indices, mesh.material_id, has_normals, false)
+                .map_err(|err| in_file(path, err))?;
+        }
+        data.validate(path)
+    }
+
+    // Meshes are placed with their node transforms from the default scene, or the
+    // first one, and taken as they are when the file has no scenes.
+    pub fn load_gltf(path: &Path) -> Result<Self, Error> {
+        let (document, buffers, _) = gltf::import(path).map_err(invalid_data)?;
+        let mut data = Self {
+            name: file_name(path),
+            ..Default::default()
+        };
+        let scene = document.default_scene().or_else(|| document.scenes().next());
+        match scene {
+            Some(scene) => {
+                let mut nodes: Vec<(gltf::Node, Mat4)> = scene
+                    .nodes()
+                    .map(|node| (node, Mat4::IDENTITY))
+                    .collect();
+                while let Some((node, parent)) = nodes.pop() {
+                    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+                    if let Some(mesh) = node.mesh() {
+                        data.append_gltf_mesh(&mesh, &buffers, transform)
+                            .map_err(|err| in_file(path, err))?;
+                    }
+                    nodes.extend(node.children().map(|child| (child, transform)));
+                }
+            }
+            None => {
+                for mesh in document.meshes() {
+                    data.append_gltf_mesh(&mesh, &buffers, Mat4::IDENTITY)
+                        .map_err(|err| in_file(path, err))?;
+                }
+            }
+        }
+        data.validate(path)
+    }
+
+    fn append_gltf_mesh(
+        &mut self,
+        mesh: &gltf::Mesh,
+        buffers: &[gltf::buffer::Data],
+        transform: Mat4,
+    ) -> Result<(), Error> {
+        let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+        let name = mesh.name().unwrap_or("mesh").to_string();
+        for (i, primitive) in mesh.primitives().enumerate() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                warn!("Skipping {name} primitive {i}, only triangle lists are supported");
+                continue;
+            }
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()][..]));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let mut vertices: Vec<Vertex> = positions
+                .map(|position| Vertex {
+                    position: transform.transform_point3(position.into()).to_array(),
+                    ..Default::default()
+                })
+                .collect();
+            let normals = reader.read_normals();
+            let has_normals = normals.is_some();
+            for (vertex, normal) in vertices.iter_mut().zip(normals.into_iter().flatten()) {
+                vertex.normal = (normal_matrix * Vec3::from(normal)).normalize_or_zero().to_array();
+            }
+            if let Some(uvs) = reader.read_tex_coords(0) {
+                for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+                    vertex.uv = uv;
+                }
+            }
+            let tangents = reader.read_tangents();
+            let has_tangents = tangents.is_some() && has_normals;
+            for (vertex, tangent) in vertices.iter_mut().zip(tangents.into_iter().flatten()) {
+                let xyz = transform.transform_vector3(Vec4::from(tangent).truncate());
+                vertex.tangent = xyz.normalize_or_zero().extend(tangent[3]).to_array();
+            }
+            let indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..vertices.len() as u32).collect(),
+            };
+            let name = format!("{name} {i}");
+            let material = primitive.material().index();
+            self.append(name, vertices, indices, material, has_normals, has_tangents)?;
+        }
+        Ok(())
+    }
+
+    // Indices come straight from the file, they are checked before anything indexes
+    // `vertices` with them.
+    fn append(
+        &mut self,
+        name: String,
+        mut vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        material: Option<usize>,
+        has_normals: bool,
+        has_tangents: bool,
+    ) -> Result<(), Error> {
+        if let Some(index) = indices.iter().find(|index| **index as usize >= vertices.len()) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{name} references vertex {index} of {}", vertices.len()),
+            ));
+        }
+        if !has_normals {
+            generate_normals(&mut vertices, &indices);
+        }
+        if !has_tangents {
+            generate_tangents(&mut vertices, &indices);
+        }
+        let base_vertex = self.vertices.len() as u32;
+        self.submeshes.push(Submesh {
+            name,
+            first_index: self.indices.len() as u32,
+            index_count: indices.len() as u32,
+            material,
+        });
+        self.indices
+            .extend(indices.into_iter().map(|index| base_vertex + index));
+        self.vertices.extend(vertices);
+        Ok(())
+    }
+
+    fn validate(self, path: &Path) -> Result<Self, Error> {
+        if self.indices.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} has no triangles", path.display()),
+            ));
+        }
+        debug!(
+            "Loaded {} with {} vertices, {} indices in {} submeshes",
+            path.display(),
+            self.vertices.len(),
+            self.indices.len(),
+            self.submeshes.len()
+        );
+        Ok(self)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        let (min, max) = self.vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), vertex| {
+                let position = Vec3::from(vertex.position);
+                (min.min(position), max.max(position))
+            },
+        );
+        Bounds { min, max }
+    }
+}
+
+// Geometry on the GPU, drawn with `RenderContext::draw_meshes`. Buffers are freed on
+// drop, so release meshes in `Application::shutdown` at the latest, or hand them to
+// `Engine::retire` while frames may still be using them.
+pub struct Mesh {
+    pub name: String,
+    pub vertex_buffer: AllocatedBuffer,
+    pub index_buffer: AllocatedBuffer,
+    pub submeshes: Vec<Submesh>,
+    pub index_count: u32,
+    pub bounds: Bounds,
+}
+
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) }
+}
+
+fn create_device_buffer(
+    allocator: &Arc<Allocator>,
+    size: DeviceSize,
+    usage: BufferUsageFlags,
+) -> Result<AllocatedBuffer, Error> {
+    create_allocated_buffer(
+        allocator,
+        size,
+        usage | BufferUsageFlags::TRANSFER_DST,
+        MemoryUsage::AutoPreferDevice,
+        AllocationCreateFlags::empty(),
+        AllocationCategory::Buffer,
+    )
+}
+
+// Copies `data` into device local vertex and index buffers through one staging
+// buffer, waiting for the copy to finish.
+pub fn upload_mesh(
+    allocator: &Arc<Allocator>,
+    immediate: &ImmediateSubmit,
+    debug_utils: &DebugUtils,
+    data: &MeshData,
+) -> Result<Mesh, Error> {
+    let vertex_bytes = as_bytes(&data.vertices);
+    let index_bytes = as_bytes(&data.indices);
+    if vertex_bytes.is_empty() || index_bytes.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "mesh has no geometry"));
+    }
+    let vertex_size = vertex_bytes.len() as DeviceSize;
+    let index_size = index_bytes.len() as DeviceSize;
+    // Storage usage so compute passes can read the geometry as well
+    let vertex_buffer = create_device_buffer(
+        allocator,
+        vertex_size,
+        BufferUsageFlags::VERTEX_BUFFER | BufferUsageFlags::STORAGE_BUFFER,
+    )?;
+    let index_buffer = create_device_buffer(
+        allocator,
+        index_size,
+        BufferUsageFlags::INDEX_BUFFER | BufferUsageFlags::STORAGE_BUFFER,
+    )?;
+    let mut staging_buffer = create_staging_buffer(allocator, vertex_size + index_size)?;
+    staging_buffer.write(0, vertex_bytes);
+    staging_buffer.write(vertex_size, index_bytes);
+    debug_utils.name(vertex_buffer.buffer, &format!("{} vertices", data.name));
+    debug_utils.name(index_buffer.buffer, &format!("{} indices", data.name));
+    debug_utils.name(staging_buffer.buffer, &format!("{} staging buffer", data.name));
+
+    immediate.submit(|device, command_buffer| unsafe {
+        device.cmd_copy_buffer(
+            command_buffer,
+            staging_buffer.buffer,
+            vertex_buffer.buffer,
+            &[BufferCopy::default().size(vertex_size)],
+        );
+        device.cmd_copy_buffer(
+            command_buffer,
+            staging_buffer.buffer,
+            index_buffer.buffer,
+            &[BufferCopy::default().src_offset(vertex_size).size(index_size)],
+        );
+    });
+
+    Ok(Mesh {
+        name: data.name.clone(),
+        vertex_buffer,
+        index_buffer,
+        submeshes: data.submeshes.clone(),
+        index_count: data.indices.len() as u32,
+        bounds: data.bounds(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    fn triangle() -> Vec<Vertex> {
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .map(|position| Vertex {
+                position,
+                ..Default::default()
+            })
+            .to_vec()
+    }
+
+    #[test]
+    fn append_generates_normals_and_offsets_indices() {
+        let mut data = MeshData::default();
+        data.append("a".into(), triangle(), vec![0, 1, 2], None, false, false)
+            .unwrap();
+        data.append("b".into(), triangle(), vec![0, 1, 2], Some(1), false, false)
+            .unwrap();
+        assert_eq!(data.indices, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(data.submeshes[1].first_index, 3);
+        assert_eq!(data.vertices[0].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn append_rejects_out_of_range_indices() {
+        let mut data = MeshData::default();
+        let err = data
+            .append("broken".into(), triangle(), vec![0, 1, 3], None, false, false)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(data.vertices.is_empty() && data.submeshes.is_empty());
+    }
+
+    #[test]
+    fn load_rejects_out_of_range_indices() {
+        let path = env::temp_dir().join(format!("wurmple_broken_{}.obj", process::id()));
+        fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 9\n").unwrap();
+        let result = MeshData::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,392 @@
+use std::{io::Error, mem::size_of, slice, sync::Arc};
+
+use ash::{
+    vk::{
+        AccessFlags, ClearDepthStencilValue, ClearValue, DependencyFlags, DescriptorSetLayout,
+        DescriptorSetLayoutCreateFlags, DescriptorType, Extent2D, Format, Framebuffer,
+        FramebufferCreateInfo, ImageAspectFlags, ImageUsageFlags, IndexType, MemoryBarrier,
+        Offset2D, Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout, PipelineStageFlags,
+        DeviceSize, Rect2D, RenderPass, RenderPassBeginInfo, ShaderStageFlags, SubpassContents,
+        Viewport,
+    },
+    Device,
+};
+use glam::{Mat4, Vec4};
+use vk_mem::{Alloc, AllocationCreateInfo, Allocator, MemoryUsage};
+
+use crate::application::RenderContext;
+
+use super::{
+    allocated_image::AllocatedImage,
+    camera::depth_clear_value,
+    components::{
+        create_mesh_pipeline, create_mesh_pipeline_layout, create_mesh_render_pass,
+        write_storage_buffer, DescriptorLayoutBuilder, ShaderRegistry,
+    },
+    debug_utils::DebugUtils,
+    handles::Owned,
+    image_ops::{image_create_info, image_view_create_info},
+    indirect::{IndirectDraws, INDIRECT_COMMAND_SIZE},
+    memory_stats::{track_allocation, AllocationCategory},
+    mesh::Mesh,
+};
+
+const DEPTH_FORMAT: Format = Format::D32_SFLOAT;
+// Where mesh_instanced.vert finds its instances
+const INSTANCE_SET: u32 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct MeshPushConstants {
+    model: Mat4,
+    color: Vec4,
+}
+
+// One mesh to draw, every submesh is drawn with the same transform and color.
+#[derive(Clone, Copy)]
+pub struct MeshDraw<'a> {
+    pub mesh: &'a Mesh,
+    pub transform: Mat4,
+    pub color: Vec4,
+}
+
+impl<'a> MeshDraw<'a> {
+    pub fn new(mesh: &'a Mesh, transform: Mat4) -> Self {
+        Self {
+            mesh,
+            transform,
+            color: Vec4::ONE,
+        }
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+fn create_depth_image(
+    device: &Arc<Device>,
+    allocator: &Arc<Allocator>,
+    draw_image: &AllocatedImage,
+) -> AllocatedImage {
+    let image_create_info = image_create_info(
+        DEPTH_FORMAT,
+        ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        draw_image.extent,
+    );
+    let allocation_create_info = AllocationCreateInfo {
+        usage: MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    let (image, allocation) = unsafe {
+        allocator
+            .create_image(&image_create_info, &allocation_create_info)
+            .unwrap()
+    };
+    track_allocation(allocator, &allocation, AllocationCategory::Image);
+    let image_view_create_info = image_view_create_info(image, DEPTH_FORMAT, ImageAspectFlags::DEPTH);
+    let image_view = unsafe {
+        device
+            .create_image_view(&image_view_create_info, None)
+            .unwrap()
+    };
+    AllocatedImage::new(
+        device.clone(),
+        allocator.clone(),
+        image,
+        image_view,
+        allocation,
+        draw_image.extent,
+        DEPTH_FORMAT,
+    )
+}
+
+// A basic lit, opaque pass for meshes straight into the draw image, with its own
+// depth buffer. Holds a pipeline for each depth convention and picks the one that
+// matches the camera. `draw` pushes one transform per mesh, `draw_indirect` reads
+// them from an instance buffer.
+pub struct MeshRenderer {
+    framebuffer: Owned<Framebuffer>,
+    depth_image: AllocatedImage,
+    pipelines: [Owned<Pipeline>; 2],
+    instanced_pipelines: [Owned<Pipeline>; 2],
+    // Shared by both kinds of pipeline, the push constants go unused when instanced
+    pipeline_layout: Owned<PipelineLayout>,
+    instance_layout: Owned<DescriptorSetLayout>,
+    render_pass: Owned<RenderPass>,
+    extent: Extent2D,
+    // Without the multiDrawIndirect feature every command is its own draw call
+    multi_draw_indirect: bool,
+}
+
+impl MeshRenderer {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &Arc<Device>,
+        allocator: &Arc<Allocator>,
+        draw_image: &AllocatedImage,
+        camera_layout: DescriptorSetLayout,
+        shader_registry: &ShaderRegistry,
+        pipeline_cache: PipelineCache,
+        multi_draw_indirect: bool,
+        debug_utils: &DebugUtils,
+    ) -> Result<Self, Error> {
+        let render_pass = Owned::new(
+            device.clone(),
+            create_mesh_render_pass(device, draw_image.image_format, DEPTH_FORMAT),
+        );
+        let mut layout_builder = DescriptorLayoutBuilder::new();
+        layout_builder.add_binding(0, DescriptorType::STORAGE_BUFFER);
+        let instance_layout = layout_builder.build(
+            device.clone(),
+            ShaderStageFlags::VERTEX,
+            DescriptorSetLayoutCreateFlags::empty(),
+        );
+        let pipeline_layout = create_mesh_pipeline_layout(
+            device.clone(),
+            &[camera_layout, *instance_layout],
+            size_of::<MeshPushConstants>() as u32,
+        );
+        let create_pipelines = |vertex_shader: &str| -> Result<[Owned<Pipeline>; 2], Error> {
+            let [pipeline, reverse_z_pipeline] = [false, true].map(|reverse_z| {
+                create_mesh_pipeline(
+                    device.clone(),
+                    &render_pass,
+                    *pipeline_layout,
+                    shader_registry,
+                    pipeline_cache,
+                    vertex_shader,
+                    reverse_z,
+                )
+            });
+            Ok([pipeline?, reverse_z_pipeline?])
+        };
+        let pipelines = create_pipelines("mesh.vert")?;
+        let instanced_pipelines = create_pipelines("mesh_instanced.vert")?;
+        let depth_image = create_depth_image(device, allocator, draw_image);
+        let extent = Extent2D {
+            width: draw_image.extent.width,
+            height: draw_image.extent.height,
+        };
+        let attachments = [draw_image.image_view, depth_image.image_view];
+        let framebuffer_create_info = FramebufferCreateInfo::default()
+            .width(extent.width)
+            .height(extent.height)
+            .render_pass(*render_pass)
+            .attachments(&attachments)
+            .layers(1);
+        let framebuffer = Owned::new(device.clone(), unsafe {
+            device
+                .create_framebuffer(&framebuffer_create_info, None)
+                .unwrap()
+        });
+
+        debug_utils.name(*render_pass, "mesh render pass");
+        debug_utils.name(*pipeline_layout, "mesh pipeline layout");
+        debug_utils.name(*pipelines[0], "mesh pipeline");
+        debug_utils.name(*pipelines[1], "mesh pipeline reverse z");
+        debug_utils.name(*instanced_pipelines[0], "instanced mesh pipeline");
+        debug_utils.name(*instanced_pipelines[1], "instanced mesh pipeline reverse z");
+        debug_utils.name(*instance_layout, "mesh instance descriptor layout");
+        debug_utils.name(depth_image.image, "mesh depth image");
+        debug_utils.name(depth_image.image_view, "mesh depth image view");
+        debug_utils.name(*framebuffer, "mesh framebuffer");
+        Ok(Self {
+            framebuffer,
+            depth_image,
+            pipelines,
+            instanced_pipelines,
+            pipeline_layout,
+            instance_layout,
+            render_pass,
+            extent,
+            multi_draw_indirect,
+        })
+    }
+
+    // Starts the pass with `pipelines` bound for the camera's depth convention and the
+    // camera at CAMERA_SET. Clears the depth buffer.
+    fn begin(&self, context: &RenderContext, pipelines: &[Owned<Pipeline>; 2]) {
+        let device = context.device;
+        let command_buffer = context.command_buffer;
+        let reverse_z = context.projection.reverse_z;
+        let clear_values = [
+            ClearValue::default(),
+            ClearValue {
+                depth_stencil: ClearDepthStencilValue {
+                    depth: depth_clear_value(reverse_z),
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_area = Rect2D {
+            offset: Offset2D::default(),
+            extent: self.extent,
+        };
+        let render_pass_info = RenderPassBeginInfo::default()
+            .render_pass(*self.render_pass)
+            .framebuffer(*self.framebuffer)
+            .render_area(render_area)
+            .clear_values(&clear_values);
+        let viewport = Viewport::default()
+            .width(self.extent.width as f32)
+            .height(self.extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+        unsafe {
+            device.cmd_begin_render_pass(command_buffer, &render_pass_info, SubpassContents::INLINE);
+            device.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                *pipelines[reverse_z as usize],
+            );
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+        }
+        context.bind_camera(PipelineBindPoint::GRAPHICS, *self.pipeline_layout);
+    }
+
+    fn bind_mesh(&self, context: &RenderContext, mesh: &Mesh) {
+        unsafe {
+            context.device.cmd_bind_vertex_buffers(
+                context.command_buffer,
+                0,
+                &[mesh.vertex_buffer.buffer],
+                &[0],
+            );
+            context.device.cmd_bind_index_buffer(
+                context.command_buffer,
+                mesh.index_buffer.buffer,
+                0,
+                IndexType::UINT32,
+            );
+        }
+    }
+
+    // Records one render pass drawing every mesh in `draws`. The depth buffer is
+    // cleared at the start of each call.
+    pub fn draw(&self, context: &RenderContext, draws: &[MeshDraw]) {
+        if draws.is_empty() {
+            return;
+        }
+        let device = context.device;
+        let command_buffer = context.command_buffer;
+        self.begin(context, &self.pipelines);
+        for draw in draws {
+            let push_constants = MeshPushConstants {
+                model: draw.transform,
+                color: draw.color,
+            };
+            let bytes = unsafe {
+                slice::from_raw_parts(
+                    &push_constants as *const MeshPushConstants as *const u8,
+                    size_of::<MeshPushConstants>(),
+                )
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    command_buffer,
+                    *self.pipeline_layout,
+                    ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                    0,
+                    bytes,
+                );
+            }
+            self.bind_mesh(context, draw.mesh);
+            for submesh in &draw.mesh.submeshes {
+                unsafe {
+                    device.cmd_draw_indexed(
+                        command_buffer,
+                        submesh.index_count,
+                        1,
+                        submesh.first_index,
+                        0,
+                        0,
+                    );
+                }
+            }
+        }
+        unsafe { device.cmd_end_render_pass(command_buffer) };
+    }
+
+    // Like `draw`, with one vkCmdDrawIndexedIndirect per batch. Compute shaders may
+    // have written the instances or commands earlier in the frame, their writes are
+    // made visible first.
+    pub fn draw_indirect(&self, context: &RenderContext, draws: &IndirectDraws) {
+        if draws.is_empty() {
+            return;
+        }
+        let device = context.device;
+        let command_buffer = context.command_buffer;
+        let instance_set = context.allocate_descriptor_set(*self.instance_layout);
+        write_storage_buffer(
+            device,
+            instance_set,
+            0,
+            draws.instances.buffer,
+            draws.instances.offset,
+            draws.instances.size,
+        );
+        let barrier = MemoryBarrier::default()
+            .src_access_mask(AccessFlags::SHADER_WRITE)
+            .dst_access_mask(AccessFlags::INDIRECT_COMMAND_READ | AccessFlags::SHADER_READ);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::DRAW_INDIRECT | PipelineStageFlags::VERTEX_SHADER,
+                DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
+        }
+        self.begin(context, &self.instanced_pipelines);
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                *self.pipeline_layout,
+                INSTANCE_SET,
+                &[instance_set],
+                &[],
+            );
+        }
+        for batch in &draws.batches {
+            self.bind_mesh(context, batch.mesh);
+            let offset = draws.commands.offset
+                + (batch.first_command * INDIRECT_COMMAND_SIZE) as DeviceSize;
+            unsafe {
+                if self.multi_draw_indirect {
+                    device.cmd_draw_indexed_indirect(
+                        command_buffer,
+                        draws.commands.buffer,
+                        offset,
+                        batch.command_count,
+                        INDIRECT_COMMAND_SIZE,
+                    );
+                } else {
+                    for command in 0..batch.command_count {
+                        device.cmd_draw_indexed_indirect(
+                            command_buffer,
+                            draws.commands.buffer,
+                            offset + (command * INDIRECT_COMMAND_SIZE) as DeviceSize,
+                            1,
+                            INDIRECT_COMMAND_SIZE,
+                        );
+                    }
+                }
+            }
+        }
+        unsafe { device.cmd_end_render_pass(command_buffer) };
+    }
+
+    pub fn render_pass(&self) -> RenderPass {
+        *self.render_pass
+    }
+
+    pub fn depth_format(&self) -> Format {
+        self.depth_image.image_format
+    }
+}
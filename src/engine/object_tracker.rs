@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    panic::Location,
+    sync::{Mutex, OnceLock},
+};
+
+use ash::vk::{Handle, ObjectType};
+use log::error;
+
+#[derive(Clone, Debug)]
+pub struct TrackedObject {
+    pub object_type: ObjectType,
+    pub handle: u64,
+    // Empty until the object is given a debug name
+    pub name: String,
+    pub location: &'static Location<'static>,
+}
+
+impl fmt::Display for TrackedObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {:#x}", self.object_type, self.handle)?;
+        if !self.name.is_empty() {
+            write!(f, " \"{}\"", self.name)?;
+        }
+        write!(f, " created at {}", self.location)
+    }
+}
+
+// Like the allocation counters, the registry is process wide so objects can be
+// tracked wherever they are created without threading it through every call.
+fn objects() -> &'static Mutex<HashMap<(ObjectType, u64), TrackedObject>> {
+    static OBJECTS: OnceLock<Mutex<HashMap<(ObjectType, u64), TrackedObject>>> = OnceLock::new();
+    OBJECTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Called by the owning wrappers (`Owned`, `Swapchain`, `Surface`, the allocated buffers
+// and images) when they take a handle, which `untrack` in their `Drop`.
+#[track_caller]
+pub fn track<H: Handle>(handle: H) {
+    let object = TrackedObject {
+        object_type: H::TYPE,
+        handle: handle.as_raw(),
+        name: String::new(),
+        location: Location::caller(),
+    };
+    objects()
+        .lock()
+        .unwrap()
+        .insert((object.object_type, object.handle), object);
+}
+
+// Attaches a debug name to the report of an already tracked object, untracked
+// handles are left alone.
+pub fn set_name<H: Handle>(handle: H, name: &str) {
+    if let Some(object) = objects()
+        .lock()
+        .unwrap()
+        .get_mut(&(H::TYPE, handle.as_raw()))
+    {
+        object.name = name.to_string();
+    }
+}
+
+pub fn untrack<H: Handle>(handle: H) {
+    objects().lock().unwrap().remove(&(H::TYPE, handle.as_raw()));
+}
+
+pub fn live_objects() -> Vec<TrackedObject> {
+    let mut objects: Vec<TrackedObject> = objects().lock().unwrap().values().cloned().collect();
+    objects.sort_by_key(|object| (object.location.file(), object.location.line()));
+    objects
+}
+
+// Logs every object that is still alive, meant to be called right before the device
+// is destroyed. Returns the number of leaked objects.
+pub fn report_leaks() -> usize {
+    let leaks = live_objects();
+    for leak in &leaks {
+        error!("Leaked {leak}");
+    }
+    leaks.len()
+}
+
+pub fn assert_no_leaks() {
+    let leaks = live_objects();
+    assert!(
+        leaks.is_empty(),
+        "{} leaked Vulkan objects:\n{}",
+        leaks.len(),
+        leaks
+            .iter()
+            .map(|leak| leak.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk::{Fence, Semaphore};
+
+    use super::*;
+
+    fn find<H: Handle>(handle: H) -> Option<TrackedObject> {
+        let key = (H::TYPE, handle.as_raw());
+        live_objects()
+            .into_iter()
+            .find(|object| (object.object_type, object.handle) == key)
+    }
+
+    #[test]
+    fn names_attach_to_tracked_objects_only() {
+        let fence = Fence::from_raw(0xf00d_0001);
+        let semaphore = Semaphore::from_raw(0xf00d_0002);
+        track(fence);
+        assert_eq!(find(fence).unwrap().name, "");
+
+        set_name(fence, "frame fence");
+        set_name(semaphore, "never tracked");
+        assert_eq!(find(fence).unwrap().name, "frame fence");
+        assert!(find(semaphore).is_none());
+
+        untrack(fence);
+        assert!(find(fence).is_none());
+    }
+}
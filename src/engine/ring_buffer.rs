@@ -0,0 +1,223 @@
+use std::{cell::RefCell, io::Error, mem::size_of_val, slice, sync::Arc};
+
+use ash::vk::{Buffer, BufferUsageFlags, DeviceSize};
+use log::debug;
+use vk_mem::Allocator;
+
+use super::{
+    allocated_buffer::{create_host_visible_buffer, AllocatedBuffer},
+    debug_utils::DebugUtils,
+};
+
+// Anything a pass might want to stream in per frame.
+const USAGE: BufferUsageFlags = BufferUsageFlags::from_raw(
+    BufferUsageFlags::UNIFORM_BUFFER.as_raw()
+        | BufferUsageFlags::STORAGE_BUFFER.as_raw()
+        | BufferUsageFlags::VERTEX_BUFFER.as_raw()
+        | BufferUsageFlags::INDEX_BUFFER.as_raw()
+        | BufferUsageFlags::INDIRECT_BUFFER.as_raw(),
+);
+
+// A chunk of this frame's ring, valid until the frame comes around again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RingAllocation {
+    pub buffer: Buffer,
+    pub offset: DeviceSize,
+    pub size: DeviceSize,
+}
+
+// Where the next allocation goes. Kept apart from the buffers so the bookkeeping can be
+// checked without a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RingCursor {
+    head: DeviceSize,
+    capacity: DeviceSize,
+    alignment: DeviceSize,
+}
+
+impl RingCursor {
+    // Offset for `size` more bytes, None once they no longer fit.
+    fn place(&mut self, size: DeviceSize) -> Option<DeviceSize> {
+        let offset = align_up(self.head, self.alignment);
+        if offset + size > self.capacity {
+            return None;
+        }
+        self.head = offset + size;
+        Some(offset)
+    }
+
+    // Starts over in a buffer at least twice as large that fits `size`, returns its
+    // capacity.
+    fn grow(&mut self, size: DeviceSize) -> DeviceSize {
+        self.capacity = (self.capacity * 2).max(size.next_power_of_two());
+        self.head = 0;
+        self.capacity
+    }
+
+    fn reset(&mut self) {
+        self.head = 0;
+    }
+}
+
+struct RingState {
+    buffer: AllocatedBuffer,
+    cursor: RingCursor,
+    // Outgrown buffers, the frame's commands may still read them until the next reset
+    retired: Vec<AllocatedBuffer>,
+}
+
+// Persistently mapped scratch memory for one frame in flight. Allocations are bumped
+// off the front and all of them are released at once by `reset`, which may only be
+// called once the frame's render fence has signalled. Takes `&self` so passes can
+// allocate while the rest of the frame is borrowed.
+pub struct RingBuffer {
+    state: RefCell<RingState>,
+    allocator: Arc<Allocator>,
+    debug_utils: DebugUtils,
+    name: String,
+}
+
+fn align_up(value: DeviceSize, alignment: DeviceSize) -> DeviceSize {
+    value.div_ceil(alignment) * alignment
+}
+
+impl RingBuffer {
+    // Every allocation starts at a multiple of `alignment`, pass the device's
+    // minUniformBufferOffsetAlignment so any chunk can back a uniform buffer.
+    pub fn new(
+        allocator: &Arc<Allocator>,
+        debug_utils: &DebugUtils,
+        name: &str,
+        size: DeviceSize,
+        alignment: DeviceSize,
+    ) -> Result<Self, Error> {
+        let buffer = create_host_visible_buffer(allocator, size, USAGE)?;
+        debug_utils.name(buffer.buffer, name);
+        Ok(Self {
+            state: RefCell::new(RingState {
+                buffer,
+                cursor: RingCursor {
+                    head: 0,
+                    capacity: size,
+                    alignment: alignment.max(4),
+                },
+                retired: Vec::new(),
+            }),
+            allocator: allocator.clone(),
+            debug_utils: debug_utils.clone(),
+            name: name.to_string(),
+        })
+    }
+
+    // Reserves `size` bytes. Running out doesn't fail, the ring is replaced with one at
+    // least twice as large and the old one is kept alive until the next reset.
+    pub fn allocate(&self, size: DeviceSize) -> RingAllocation {
+        let mut state = self.state.borrow_mut();
+        let offset = match state.cursor.place(size) {
+            Some(offset) => offset,
+            None => {
+                let capacity = state.cursor.grow(size);
+                debug!("Growing {} to {capacity} bytes", self.name);
+                let buffer = create_host_visible_buffer(&self.allocator, capacity, USAGE).unwrap();
+                self.debug_utils.name(buffer.buffer, &self.name);
+                let outgrown = std::mem::replace(&mut state.buffer, buffer);
+                state.retired.push(outgrown);
+                state.cursor.place(size).unwrap()
+            }
+        };
+        RingAllocation {
+            buffer: state.buffer.buffer,
+            offset,
+            size,
+        }
+    }
+
+    pub fn push<T: Copy>(&self, data: &[T]) -> RingAllocation {
+        let bytes = unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) };
+        let allocation = self.allocate(bytes.len() as DeviceSize);
+        self.state.borrow_mut().buffer.write(allocation.offset, bytes);
+        allocation
+    }
+
+    pub fn push_value<T: Copy>(&self, value: &T) -> RingAllocation {
+        self.push(slice::from_ref(value))
+    }
+
+    pub fn reset(&self) {
+        let mut state = self.state.borrow_mut();
+        state.cursor.reset();
+        state.retired.clear();
+    }
+
+    pub fn used(&self) -> DeviceSize {
+        self.state.borrow().cursor.head
+    }
+
+    pub fn capacity(&self) -> DeviceSize {
+        self.state.borrow().cursor.capacity
+    }
+
+    pub fn alignment(&self) -> DeviceSize {
+        self.state.borrow().cursor.alignment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(capacity: DeviceSize, alignment: DeviceSize) -> RingCursor {
+        RingCursor {
+            head: 0,
+            capacity,
+            alignment,
+        }
+    }
+
+    #[test]
+    fn consecutive_allocations_are_aligned() {
+        let mut ring = cursor(4096, 256);
+        let offsets: Vec<_> = [4, 100, 256, 1]
+            .into_iter()
+            .map(|size| ring.place(size).unwrap())
+            .collect();
+        assert_eq!(offsets, [0, 256, 512, 768]);
+        assert_eq!(ring.head, 769);
+    }
+
+    #[test]
+    fn fills_up_to_the_last_byte() {
+        let mut ring = cursor(1024, 256);
+        assert_eq!(ring.place(768), Some(0));
+        assert_eq!(ring.place(256), Some(768));
+        assert_eq!(ring.place(1), None);
+        // A failed placement leaves the cursor where it was
+        assert_eq!(ring.head, 1024);
+    }
+
+    #[test]
+    fn grows_when_an_allocation_does_not_fit() {
+        let mut ring = cursor(1024, 256);
+        ring.place(900).unwrap();
+        assert_eq!(ring.place(200), None);
+        assert_eq!(ring.grow(200), 2048);
+        assert_eq!(ring.place(200), Some(0));
+
+        // Doubling isn't always enough
+        assert_eq!(ring.place(5000), None);
+        assert_eq!(ring.grow(5000), 8192);
+        assert_eq!(ring.place(5000), Some(0));
+    }
+
+    #[test]
+    fn reset_starts_the_next_frame_at_zero() {
+        let mut ring = cursor(1024, 256);
+        ring.place(900).unwrap();
+        ring.grow(200);
+        ring.place(200).unwrap();
+        ring.reset();
+        // The grown capacity stays, so the next frame doesn't have to grow again
+        assert_eq!(ring, cursor(2048, 256));
+        assert_eq!(ring.place(1500), Some(0));
+    }
+}
@@ -0,0 +1,787 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+    mem,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use log::warn;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::application::{Application, RenderContext};
+
+use super::{
+    camera::{Camera, Projection},
+    input::InputState,
+    mesh::Mesh,
+    mesh_renderer::MeshDraw,
+    Engine,
+};
+
+// Stays valid until its node is removed, ids are never reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+fn white() -> Vec4 {
+    Vec4::ONE
+}
+
+// Geometry drawn at the node. A relative `path` is resolved against the directory of
+// the scene file, see `Scene::resolve`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MeshComponent {
+    pub path: PathBuf,
+    #[serde(default = "white")]
+    pub color: Vec4,
+}
+
+impl MeshComponent {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            color: white(),
+        }
+    }
+}
+
+// Directional and spot lights shine along the node's -z.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LightKind {
+    Directional,
+    Point { range: f32 },
+    Spot { range: f32, inner_angle: f32, outer_angle: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn new(kind: LightKind) -> Self {
+        Self {
+            kind,
+            color: Vec3::ONE,
+            intensity: 1.0,
+        }
+    }
+}
+
+// A transform in its parent's space plus whatever is attached to it. Parent, children
+// and the world matrix are owned by the `Scene`.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    pub mesh: Option<MeshComponent>,
+    pub camera: Option<Projection>,
+    pub light: Option<Light>,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Mat4,
+    // Set when the local transform changed since the last `Scene::update_transforms`
+    dirty: bool,
+}
+
+impl Node {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            mesh: None,
+            camera: None,
+            light: None,
+            transform: Transform::IDENTITY,
+            parent: None,
+            children: Vec::new(),
+            world: Mat4::IDENTITY,
+            dirty: true,
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh: MeshComponent) -> Self {
+        self.mesh = Some(mesh);
+        self
+    }
+
+    pub fn with_camera(mut self, projection: Projection) -> Self {
+        self.camera = Some(projection);
+        self
+    }
+
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.light = Some(light);
+        self
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.dirty = true;
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    // As of the last `Scene::update_transforms`.
+    pub fn world_transform(&self) -> Mat4 {
+        self.world
+    }
+}
+
+// What scene files contain: the node tree with children nested in their parent.
+#[derive(Serialize, Deserialize)]
+struct NodeDesc {
+    name: String,
+    #[serde(default)]
+    transform: Transform,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mesh: Option<MeshComponent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    camera: Option<Projection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    light: Option<Light>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    children: Vec<NodeDesc>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SceneDesc {
+    nodes: Vec<NodeDesc>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneFormat {
+    Ron,
+    Json,
+}
+
+impl SceneFormat {
+    // From the extension, .ron or .json.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("ron") => Ok(Self::Ron),
+            Some("json") => Ok(Self::Json),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{} is not a .ron or .json scene", path.display()),
+            )),
+        }
+    }
+}
+
+// A hierarchy of nodes. World matrices are cached and only recomputed for nodes whose
+// own or an ancestor's transform changed, call `update_transforms` once a frame
+// before reading them.
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    nodes: Vec<Option<Node>>,
+    roots: Vec<NodeId>,
+    // Where the scene was loaded from, relative mesh paths start here
+    directory: Option<PathBuf>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, parent: Option<NodeId>, mut node: Node) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = parent.filter(|parent| self.get(*parent).is_some());
+        node.children.clear();
+        node.dirty = true;
+        match node.parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes.push(Some(node));
+        id
+    }
+
+    // Removes the node together with everything below it.
+    pub fn remove(&mut self, id: NodeId) -> bool {
+        let Some(node) = self.get(id) else {
+            return false;
+        };
+        match node.parent {
+            Some(parent) => self.node_mut(parent).children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes[id.0].take() {
+                stack.extend(node.children);
+            }
+        }
+        true
+    }
+
+    // Moves `id` under `parent`, or to the top level for `None`, keeping its local
+    // transform. Fails if that would make a node its own ancestor.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), Error> {
+        if self.get(id).is_none() || parent.is_some_and(|parent| self.get(parent).is_none()) {
+            return Err(Error::new(ErrorKind::NotFound, "no such node"));
+        }
+        let mut ancestor = parent;
+        while let Some(current) = ancestor {
+            if current == id {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "a node can't be parented to itself or its descendants",
+                ));
+            }
+            ancestor = self.node(current).parent;
+        }
+        match self.node(id).parent {
+            Some(old) => self.node_mut(old).children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = self.node_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0).and_then(Option::as_ref)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0).and_then(Option::as_mut)
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        self.get(id).unwrap()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.get_mut(id).unwrap()
+    }
+
+    // The first node called `name`, depth first.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.depth_first().find(|id| self.node(*id).name == name)
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    // Every node, parents before their children.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.depth_first().map(|id| (id, self.node(id)))
+    }
+
+    fn depth_first(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.node(id).children.iter().rev());
+            Some(id)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    pub fn update_transforms(&mut self) {
+        // (node, parent's world matrix, whether the parent's matrix changed)
+        let mut stack: Vec<(NodeId, Mat4, bool)> = self
+            .roots
+            .iter()
+            .map(|root| (*root, Mat4::IDENTITY, false))
+            .collect();
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = self.node_mut(id);
+            let changed = node.dirty || parent_changed;
+            if changed {
+                node.world = parent_world * node.transform.matrix();
+                node.dirty = false;
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().map(|child| (*child, world, changed)));
+        }
+    }
+
+    pub fn world_transform(&self, id: NodeId) -> Option<Mat4> {
+        self.get(id).map(Node::world_transform)
+    }
+
+    // The first camera node, looking down its -z.
+    pub fn camera(&self) -> Option<SceneCamera> {
+        self.iter().find_map(|(_, node)| {
+            node.camera.map(|projection| SceneCamera {
+                world: node.world,
+                projection,
+            })
+        })
+    }
+
+    // Every light with the world matrix of its node.
+    pub fn lights(&self) -> impl Iterator<Item = (Mat4, &Light)> {
+        self.iter()
+            .filter_map(|(_, node)| node.light.as_ref().map(|light| (node.world, light)))
+    }
+
+    // One draw per mesh node whose mesh is in `meshes`.
+    pub fn mesh_draws<'a>(&self, meshes: &'a SceneMeshes) -> Vec<MeshDraw<'a>> {
+        self.iter()
+            .filter_map(|(_, node)| {
+                let component = node.mesh.as_ref()?;
+                let mesh = meshes.get(&self.resolve(&component.path))?;
+                Some(MeshDraw::new(mesh, node.world).with_color(component.color))
+            })
+            .collect()
+    }
+
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    pub fn set_directory(&mut self, directory: Option<PathBuf>) {
+        self.directory = directory;
+    }
+
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        match &self.directory {
+            Some(directory) if path.is_relative() => directory.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    fn describe(&self, id: NodeId) -> NodeDesc {
+        let node = self.node(id);
+        NodeDesc {
+            name: node.name.clone(),
+            transform: node.transform,
+            mesh: node.mesh.clone(),
+            camera: node.camera,
+            light: node.light,
+            children: node.children.iter().map(|child| self.describe(*child)).collect(),
+        }
+    }
+
+    fn add_desc(&mut self, parent: Option<NodeId>, desc: NodeDesc) {
+        let node = Node {
+            mesh: desc.mesh,
+            camera: desc.camera,
+            light: desc.light,
+            ..Node::new(&desc.name).with_transform(desc.transform)
+        };
+        let id = self.add(parent, node);
+        for child in desc.children {
+            self.add_desc(Some(id), child);
+        }
+    }
+
+    pub fn from_ron(ron: &str) -> Result<Self, Error> {
+        ron::from_str(ron).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn to_ron(&self) -> Result<String, Error> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    // Reads a .ron or .json scene. Transforms are up to date on return and relative
+    // mesh paths resolve against the file's directory.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)?;
+        let mut scene = match SceneFormat::from_path(path)? {
+            SceneFormat::Ron => Self::from_ron(&text)?,
+            SceneFormat::Json => Self::from_json(&text)?,
+        };
+        scene.directory = path.parent().map(Path::to_path_buf);
+        scene.update_transforms();
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let text = match SceneFormat::from_path(path)? {
+            SceneFormat::Ron => self.to_ron()?,
+            SceneFormat::Json => self.to_json()?,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, text)
+    }
+}
+
+impl Serialize for Scene {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SceneDesc {
+            nodes: self.roots.iter().map(|root| self.describe(*root)).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Scene {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let desc = SceneDesc::deserialize(deserializer)?;
+        let mut scene = Scene::new();
+        for node in desc.nodes {
+            scene.add_desc(None, node);
+        }
+        Ok(scene)
+    }
+}
+
+// The view from a camera node. Doesn't follow the node, take a new one from
+// `Scene::camera` after moving it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneCamera {
+    pub world: Mat4,
+    pub projection: Projection,
+}
+
+impl Camera for SceneCamera {
+    fn update(&mut self, _input: &InputState, _delta: Duration, _viewport: Vec2) {}
+
+    fn view(&self) -> Mat4 {
+        self.world.inverse()
+    }
+
+    fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    fn position(&self) -> Vec3 {
+        self.world.w_axis.truncate()
+    }
+}
+
+// GPU meshes for a scene's mesh components, each file is loaded once however many
+// nodes use it. Holds `Mesh`es, so the same lifetime rules apply.
+#[derive(Default)]
+pub struct SceneMeshes {
+    meshes: HashMap<PathBuf, Mesh>,
+}
+
+impl SceneMeshes {
+    pub fn load(engine: &Engine, scene: &Scene) -> Result<Self, Error> {
+        let mut meshes = HashMap::new();
+        for (_, node) in scene.iter() {
+            let Some(component) = &node.mesh else {
+                continue;
+            };
+            let path = scene.resolve(&component.path);
+            if meshes.contains_key(&path) {
+                continue;
+            }
+            let mesh = engine.load_mesh(&path).map_err(|err| {
+                Error::new(err.kind(), format!("{}: {err}", path.display()))
+            })?;
+            meshes.insert(path, mesh);
+        }
+        Ok(Self { meshes })
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Mesh> {
+        self.meshes.get(path)
+    }
+}
+
+// Draws a scene through its camera node, enough to look at an authored scene without
+// writing an application. `testing::render_scene` renders through it as well.
+pub struct SceneView {
+    pub scene: Scene,
+    meshes: SceneMeshes,
+}
+
+impl SceneView {
+    pub fn new(scene: Scene) -> Self {
+        Self {
+            scene,
+            meshes: SceneMeshes::default(),
+        }
+    }
+}
+
+impl Application for SceneView {
+    fn init(&mut self, engine: &mut Engine) {
+        match SceneMeshes::load(engine, &self.scene) {
+            Ok(meshes) => self.meshes = meshes,
+            Err(err) => warn!("Failed to load scene meshes: {err}"),
+        }
+        self.scene.update_transforms();
+        if let Some(camera) = self.scene.camera() {
+            engine.set_camera(camera);
+        }
+    }
+
+    fn update(&mut self, _engine: &mut Engine, _delta: Duration) {
+        self.scene.update_transforms();
+    }
+
+    fn render(&mut self, context: &RenderContext) {
+        context.draw_meshes_culled(&self.scene.mesh_draws(&self.meshes));
+    }
+
+    fn shutdown(&mut self, engine: &mut Engine) {
+        engine.retire(mem::take(&mut self.meshes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix_eq(actual: Mat4, expected: Mat4) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-5),
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    fn local(scene: &Scene, id: NodeId) -> Mat4 {
+        scene.get(id).unwrap().transform().matrix()
+    }
+
+    // Everything a scene file stores, in depth first order.
+    type NodeSummary = (
+        String,
+        Option<String>,
+        Transform,
+        Option<MeshComponent>,
+        Option<Projection>,
+        Option<Light>,
+    );
+
+    fn summary(scene: &Scene) -> Vec<NodeSummary> {
+        scene
+            .iter()
+            .map(|(_, node)| {
+                let parent = node.parent().map(|parent| scene.get(parent).unwrap().name.clone());
+                let transform = *node.transform();
+                (node.name.clone(), parent, transform, node.mesh.clone(), node.camera, node.light)
+            })
+            .collect()
+    }
+
+    fn sample_scene() -> Scene {
+        let mut scene = Scene::new();
+        let root = scene.add(
+            None,
+            Node::new("root").with_transform(
+                Transform::from_translation(Vec3::new(1.0, 2.0, 3.0))
+                    .with_rotation(Quat::from_rotation_y(0.5))
+                    .with_scale(Vec3::splat(2.0)),
+            ),
+        );
+        let mut cube = MeshComponent::new("meshes/cube.obj");
+        cube.color = Vec4::new(1.0, 0.5, 0.25, 1.0);
+        scene.add(
+            Some(root),
+            Node::new("cube")
+                .with_transform(Transform::from_translation(Vec3::X))
+                .with_mesh(cube),
+        );
+        scene.add(
+            Some(root),
+            Node::new("eye")
+                .with_transform(Transform::from_translation(Vec3::new(0.0, 1.0, 5.0)))
+                .with_camera(Projection::perspective(0.8)),
+        );
+        let lamp = scene.add(
+            Some(root),
+            Node::new("lamp").with_light(Light::new(LightKind::Spot {
+                range: 10.0,
+                inner_angle: 0.3,
+                outer_angle: 0.6,
+            })),
+        );
+        scene.add(Some(lamp), Node::new("bulb"));
+        scene.add(
+            None,
+            Node::new("sun").with_light(Light::new(LightKind::Directional)),
+        );
+        scene
+    }
+
+    #[test]
+    fn children_follow_a_moved_parent() {
+        let mut scene = Scene::new();
+        let parent = scene.add(
+            None,
+            Node::new("parent").with_transform(Transform::from_translation(Vec3::X)),
+        );
+        let child = scene.add(
+            Some(parent),
+            Node::new("child").with_transform(
+                Transform::from_translation(Vec3::Y).with_scale(Vec3::splat(0.5)),
+            ),
+        );
+        scene.update_transforms();
+        assert_matrix_eq(
+            scene.world_transform(child).unwrap(),
+            local(&scene, parent) * local(&scene, child),
+        );
+
+        scene.get_mut(parent).unwrap().set_transform(
+            Transform::from_translation(Vec3::new(5.0, 0.0, 0.0))
+                .with_rotation(Quat::from_rotation_z(1.0)),
+        );
+        scene.update_transforms();
+        assert_matrix_eq(
+            scene.world_transform(child).unwrap(),
+            local(&scene, parent) * local(&scene, child),
+        );
+    }
+
+    #[test]
+    fn set_parent_moves_the_whole_subtree() {
+        let mut scene = Scene::new();
+        let a = scene.add(
+            None,
+            Node::new("a").with_transform(Transform::from_translation(Vec3::X)),
+        );
+        let b = scene.add(
+            None,
+            Node::new("b").with_transform(
+                Transform::from_translation(Vec3::Y).with_rotation(Quat::from_rotation_x(0.3)),
+            ),
+        );
+        let c = scene.add(
+            Some(b),
+            Node::new("c").with_transform(Transform::from_translation(Vec3::Z)),
+        );
+        scene.update_transforms();
+        assert_matrix_eq(
+            scene.world_transform(c).unwrap(),
+            local(&scene, b) * local(&scene, c),
+        );
+
+        scene.set_parent(b, Some(a)).unwrap();
+        scene.update_transforms();
+        assert_eq!(scene.roots(), [a]);
+        assert_matrix_eq(
+            scene.world_transform(c).unwrap(),
+            local(&scene, a) * local(&scene, b) * local(&scene, c),
+        );
+
+        scene.set_parent(b, None).unwrap();
+        scene.update_transforms();
+        assert_matrix_eq(
+            scene.world_transform(c).unwrap(),
+            local(&scene, b) * local(&scene, c),
+        );
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut scene = Scene::new();
+        let a = scene.add(None, Node::new("a"));
+        let b = scene.add(Some(a), Node::new("b"));
+        let err = scene.set_parent(a, Some(b)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(scene.set_parent(a, Some(a)).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(scene.get(b).unwrap().parent(), Some(a));
+    }
+
+    #[test]
+    fn ron_round_trips() {
+        let scene = sample_scene();
+        let loaded = Scene::from_ron(&scene.to_ron().unwrap()).unwrap();
+        assert_eq!(summary(&loaded), summary(&scene));
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let scene = sample_scene();
+        let loaded = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+        assert_eq!(summary(&loaded), summary(&scene));
+    }
+}
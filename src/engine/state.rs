@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use egui::TextureId;
+
+use super::{
+    clock::FrameTime,
+    components::{ComputeEffect, ComputePushConstants},
+    culling::CullingStats,
+    device_info::DeviceInfo,
+    memory_stats::MemoryStats,
+};
+
+pub struct EffectParameters {
+    pub name: String,
+    pub parameters: ComputePushConstants,
+    pub default_parameters: ComputePushConstants,
+}
+
+#[derive(Default)]
+pub struct EngineState {
+    pub current_effect: usize,
+    pub effects: Vec<EffectParameters>,
+    // Engine images registered with the egui renderer, e.g. "draw_image".
+    pub textures: HashMap<String, TextureId>,
+    pub device_info: DeviceInfo,
+    pub memory: MemoryStats,
+    pub frame_time: FrameTime,
+    // Trails the current frame by MAX_FRAMES, see `FrustumCuller`
+    pub culling: CullingStats,
+}
+
+impl EngineState {
+    pub fn new(effects: &[ComputeEffect]) -> Self {
+        Self {
+            current_effect: 0,
+            effects: effects
+                .iter()
+                .map(|effect| EffectParameters {
+                    name: effect.name.clone(),
+                    parameters: effect.default_parameters,
+                    default_parameters: effect.default_parameters,
+                })
+                .collect(),
+            textures: HashMap::new(),
+            device_info: DeviceInfo::default(),
+            memory: MemoryStats::default(),
+            frame_time: FrameTime::default(),
+            culling: CullingStats::default(),
+        }
+    }
+
+    pub fn current_parameters(&mut self) -> Option<&mut ComputePushConstants> {
+        self.effects
+            .get_mut(self.current_effect)
+            .map(|effect| &mut effect.parameters)
+    }
+}
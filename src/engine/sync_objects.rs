@@ -1,15 +1,20 @@
+use std::sync::Arc;
+
 use ash::{
     vk::{Fence, FenceCreateFlags, FenceCreateInfo, Semaphore, SemaphoreCreateFlags, SemaphoreCreateInfo},
     Device,
 };
 
-pub fn create_semaphore(device: &Device) -> Semaphore {
+use super::handles::Owned;
+
+pub fn create_semaphore(device: &Arc<Device>) -> Owned<Semaphore> {
     let create_info = SemaphoreCreateInfo::default().flags(SemaphoreCreateFlags::default());
-    unsafe { device.create_semaphore(&create_info, None).unwrap() }
+    let semaphore = unsafe { device.create_semaphore(&create_info, None).unwrap() };
+    Owned::new(device.clone(), semaphore)
 }
 
-pub fn create_fence(device: &Device) -> Fence {
+pub fn create_fence(device: &Arc<Device>) -> Owned<Fence> {
     let create_info = FenceCreateInfo::default().flags(FenceCreateFlags::SIGNALED);
-    unsafe { device.create_fence(&create_info, None).unwrap() }
+    let fence = unsafe { device.create_fence(&create_info, None).unwrap() };
+    Owned::new(device.clone(), fence)
 }
-
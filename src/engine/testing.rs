@@ -0,0 +1,195 @@
+use std::{io::Error, path::Path};
+
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{WindowAttributes, WindowId},
+};
+
+use crate::application::Application;
+
+use super::{
+    clock::{FrameClock, FrameTime, DEFAULT_FIXED_STEP},
+    memory_stats::{category_stats, AllocationCategory, CategoryStats},
+    object_tracker::{live_objects, TrackedObject},
+    scene::{Scene, SceneView},
+    validation::{ValidationConfig, ValidationMessage},
+    Engine,
+};
+
+#[derive(Default)]
+pub struct FrameReport {
+    // False when VK_LAYER_KHRONOS_validation could not be enabled, `messages` is then
+    // empty whatever was rendered.
+    pub validation_layer: bool,
+    pub messages: Vec<ValidationMessage>,
+    // Objects and allocations still alive after the engine was cleaned up. The tracker
+    // and the allocation counters are process wide, so these are only meaningful when
+    // nothing else in the process creates Vulkan objects at the same time. Leak tests
+    // each get their own test binary for that reason: a binary is its own process and
+    // cargo runs them one after the other.
+    pub leaks: Vec<TrackedObject>,
+    pub leaked_allocations: Vec<(AllocationCategory, CategoryStats)>,
+    // The clock and the draw image as of the last frame
+    pub time: FrameTime,
+    pub image: Vec<u8>,
+}
+
+struct FrameRunner {
+    frames: usize,
+    // Rendered one after the other, each with a fresh window and engine
+    applications: Vec<Box<dyn Application>>,
+    reports: Vec<FrameReport>,
+}
+
+impl FrameRunner {
+    fn run(&self, event_loop: &ActiveEventLoop, application: &mut dyn Application) -> FrameReport {
+        let window_attributes = WindowAttributes::default()
+            .with_visible(false)
+            .with_inner_size(PhysicalSize::new(320, 240));
+        let window = event_loop.create_window(window_attributes).unwrap();
+        let validation = ValidationConfig::from_env().with_enable_layer(true);
+        let mut engine = Engine::with_validation(&window, validation).unwrap();
+        // Frame N sees the same time on every run, whatever the machine
+        engine.set_clock(FrameClock::manual(DEFAULT_FIXED_STEP, DEFAULT_FIXED_STEP));
+        application.init(&mut engine);
+        for _ in 0..self.frames {
+            engine.update(application);
+            engine.draw(&window, application);
+        }
+        let time = engine.clock().time();
+        let image = engine.read_draw_image().unwrap();
+        application.shutdown(&mut engine);
+        let validation = engine.validation();
+        drop(engine);
+        FrameReport {
+            validation_layer: validation.layer_enabled(),
+            messages: validation.take(),
+            leaks: live_objects(),
+            leaked_allocations: AllocationCategory::ALL
+                .into_iter()
+                .map(|category| (category, category_stats(category)))
+                .filter(|(_, stats)| stats.live_allocations > 0)
+                .collect(),
+            time,
+            image,
+        }
+    }
+}
+
+impl ApplicationHandler for FrameRunner {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.reports.is_empty() {
+            return;
+        }
+        for mut application in std::mem::take(&mut self.applications) {
+            let report = self.run(event_loop, application.as_mut());
+            self.reports.push(report);
+        }
+        event_loop.exit();
+    }
+
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, _window_id: WindowId, _event: WindowEvent) {}
+}
+
+// Renders `frames` frames into a hidden window with the validation layer enabled, shuts
+// the engine down and returns every validation message reported along the way together
+// with the objects left alive. Point the loader at a software driver such as lavapipe
+// (VK_DRIVER_FILES=.../lvp_icd.x86_64.json) to run this on machines without a GPU.
+//
+// winit allows a single event loop per process, so call this once per test binary.
+pub fn render_frames(frames: usize) -> FrameReport {
+    render_applications(frames, vec![Box::new(())]).remove(0)
+}
+
+// Like `render_frames`, drawing `scene` through its camera node.
+pub fn render_scene(scene: Scene, frames: usize) -> FrameReport {
+    render_applications(frames, vec![Box::new(SceneView::new(scene))]).remove(0)
+}
+
+pub fn render_scene_file(path: &Path, frames: usize) -> Result<FrameReport, Error> {
+    Ok(render_scene(Scene::load(path)?, frames))
+}
+
+// Renders each application in turn inside the one event loop, a report per application.
+pub fn render_applications(
+    frames: usize,
+    applications: Vec<Box<dyn Application>>,
+) -> Vec<FrameReport> {
+    let mut builder = EventLoop::builder();
+    #[cfg(target_os = "linux")]
+    winit::platform::x11::EventLoopBuilderExtX11::with_any_thread(&mut builder, true);
+    let event_loop = builder.build().unwrap();
+    let count = applications.len();
+    let mut runner = FrameRunner {
+        frames,
+        applications,
+        reports: Vec::new(),
+    };
+    event_loop.run_app(&mut runner).unwrap();
+    runner.reports.resize_with(count, FrameReport::default);
+    runner.reports
+}
+
+// Renders `frames` frames twice from scratch and checks that the last one came out the
+// same both times, down to the pixel.
+pub fn assert_frames_render_reproducibly(frames: usize) {
+    let reports = render_applications(frames, vec![Box::new(()), Box::new(())]);
+    let (first, second) = (&reports[0], &reports[1]);
+    assert_eq!(first.time, second.time, "frame {frames} ran at different times");
+    assert!(!first.image.is_empty(), "frame {frames} was not read back");
+    let differing = first
+        .image
+        .iter()
+        .zip(&second.image)
+        .filter(|(a, b)| a != b)
+        .count();
+    assert!(
+        first.image.len() == second.image.len() && differing == 0,
+        "frame {frames} differs between runs in {differing} of {} bytes",
+        first.image.len()
+    );
+}
+
+pub fn assert_frames_render_without_validation_errors(frames: usize) {
+    let report = render_frames(frames);
+    assert!(
+        report.validation_layer,
+        "VK_LAYER_KHRONOS_validation did not load, install the Vulkan validation layers"
+    );
+    let errors: Vec<String> = report
+        .messages
+        .iter()
+        .filter(|message| message.is_error())
+        .map(|message| message.to_string())
+        .collect();
+    assert!(
+        errors.is_empty(),
+        "{} validation errors while rendering {frames} frames:\n{}",
+        errors.len(),
+        errors.join("\n")
+    );
+}
+
+pub fn assert_frames_render_without_leaks(frames: usize) {
+    let report = render_frames(frames);
+    let leaks: Vec<String> = report
+        .leaks
+        .iter()
+        .map(|leak| leak.to_string())
+        .chain(report.leaked_allocations.iter().map(|(category, stats)| {
+            format!(
+                "{} {category:?} allocations ({} bytes)",
+                stats.live_allocations, stats.live_bytes
+            )
+        }))
+        .collect();
+    assert!(
+        leaks.is_empty(),
+        "{} Vulkan objects or allocation categories leaked after rendering {frames} frames:\n{}",
+        leaks.len(),
+        leaks.join("\n")
+    );
+}
@@ -0,0 +1,184 @@
+use egui::{CollapsingHeader, Context, Grid, ProgressBar, ScrollArea, Ui, Window};
+
+use log::warn;
+
+use crate::engine::{
+    device_info::DeviceInfo,
+    memory_stats::{category_stats, default_report_path, AllocationCategory, MemoryStats},
+    state::EngineState,
+};
+
+use super::UI;
+
+#[derive(Default)]
+pub struct Inspector {}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        ash::vk::api_version_major(version),
+        ash::vk::api_version_minor(version),
+        ash::vk::api_version_patch(version)
+    )
+}
+
+fn rows(ui: &mut Ui, id: &str, rows: &[(&str, String)]) {
+    Grid::new(id).striped(true).show(ui, |ui| {
+        for (label, value) in rows {
+            ui.label(*label);
+            ui.monospace(value);
+            ui.end_row();
+        }
+    });
+}
+
+fn device_section(ui: &mut Ui, info: &DeviceInfo) {
+    let properties = &info.properties;
+    let name = properties
+        .device_name_as_c_str()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    rows(
+        ui,
+        "device",
+        &[
+            ("Name", name),
+            ("Type", format!("{:?}", properties.device_type)),
+            ("API version", version(properties.api_version)),
+            ("Driver version", format!("{:#x}", properties.driver_version)),
+            ("Vendor id", format!("{:#06x}", properties.vendor_id)),
+            ("Device id", format!("{:#06x}", properties.device_id)),
+        ],
+    );
+}
+
+fn limits_section(ui: &mut Ui, info: &DeviceInfo) {
+    let limits = &info.properties.limits;
+    rows(
+        ui,
+        "limits",
+        &[
+            ("Max image 2D", limits.max_image_dimension2_d.to_string()),
+            ("Max push constants", format!("{} B", limits.max_push_constants_size)),
+            ("Max bound sets", limits.max_bound_descriptor_sets.to_string()),
+            ("Max allocations", limits.max_memory_allocation_count.to_string()),
+            (
+                "Max compute group size",
+                format!("{:?}", limits.max_compute_work_group_size),
+            ),
+            (
+                "Max compute invocations",
+                limits.max_compute_work_group_invocations.to_string(),
+            ),
+            ("Max anisotropy", limits.max_sampler_anisotropy.to_string()),
+            (
+                "Min UBO alignment",
+                limits.min_uniform_buffer_offset_alignment.to_string(),
+            ),
+            ("Non coherent atom", limits.non_coherent_atom_size.to_string()),
+            ("Timestamp period", format!("{} ns", limits.timestamp_period)),
+        ],
+    );
+    CollapsingHeader::new("All limits").show(ui, |ui| {
+        ui.monospace(format!("{limits:#?}"));
+    });
+}
+
+fn swapchain_section(ui: &mut Ui, info: &DeviceInfo) {
+    let indices = &info.queue_family_indices;
+    rows(
+        ui,
+        "swapchain",
+        &[
+            ("Graphics queue family", format!("{:?}", indices.graphics_q_idx)),
+            ("Present queue family", format!("{:?}", indices.presentation_q_idx)),
+            ("Format", format!("{:?}", info.swapchain_format.format)),
+            ("Color space", format!("{:?}", info.swapchain_format.color_space)),
+            ("Present mode", format!("{:?}", info.present_mode)),
+            (
+                "Extent",
+                format!(
+                    "{}x{}",
+                    info.swapchain_extent.width, info.swapchain_extent.height
+                ),
+            ),
+            ("Images", info.swapchain_image_count.to_string()),
+        ],
+    );
+}
+
+fn memory_section(ui: &mut Ui, memory: &MemoryStats) {
+    for (idx, heap) in memory.heaps.iter().enumerate() {
+        ui.label(format!(
+            "Heap {idx} ({}), {} total, {} allocations in {} blocks",
+            if heap.device_local { "device local" } else { "host" },
+            megabytes(heap.size),
+            heap.allocation_count,
+            heap.block_count
+        ));
+        let fraction = if heap.budget > 0 {
+            heap.usage as f32 / heap.budget as f32
+        } else {
+            0.0
+        };
+        ui.add(ProgressBar::new(fraction).text(format!(
+            "{} / {}",
+            megabytes(heap.usage),
+            megabytes(heap.budget)
+        )));
+    }
+    Grid::new("categories").striped(true).show(ui, |ui| {
+        for label in ["Category", "Live", "Bytes", "Peak", "Total"] {
+            ui.strong(label);
+        }
+        ui.end_row();
+        for category in AllocationCategory::ALL {
+            let stats = category_stats(category);
+            ui.label(format!("{category:?}"));
+            ui.monospace(stats.live_allocations.to_string());
+            ui.monospace(megabytes(stats.live_bytes));
+            ui.monospace(megabytes(stats.peak_bytes));
+            ui.monospace(stats.total_allocations.to_string());
+            ui.end_row();
+        }
+    });
+    if ui.button("Write JSON report").clicked() {
+        let path = default_report_path();
+        if let Err(err) = memory.write_report(&path) {
+            warn!("Failed to write memory report to {path:?}: {err}");
+        }
+    }
+}
+
+impl UI for Inspector {
+    fn ui(&mut self, ctx: &Context, state: &mut EngineState) {
+        let (info, memory) = (&state.device_info, &state.memory);
+        Window::new("Inspector").show(ctx, |ui| {
+            ScrollArea::vertical().show(ui, |ui| {
+                CollapsingHeader::new("Device")
+                    .default_open(true)
+                    .show(ui, |ui| device_section(ui, info));
+                CollapsingHeader::new("Limits").show(ui, |ui| limits_section(ui, info));
+                CollapsingHeader::new("Extensions").show(ui, |ui| {
+                    for extension in &info.enabled_extensions {
+                        ui.monospace(extension);
+                    }
+                });
+                CollapsingHeader::new("Features").show(ui, |ui| {
+                    for feature in &info.enabled_features {
+                        ui.monospace(feature);
+                    }
+                });
+                CollapsingHeader::new("Queues and swapchain")
+                    .show(ui, |ui| swapchain_section(ui, info));
+                CollapsingHeader::new("Memory")
+                    .default_open(true)
+                    .show(ui, |ui| memory_section(ui, memory));
+            });
+        });
+    }
+}
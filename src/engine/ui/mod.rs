@@ -1,7 +1,17 @@
 use egui::Context;
 
+use super::state::EngineState;
+
+mod inspector;
+mod profiler;
+mod registry;
 mod settings;
 
-trait UI {
-   fn ui(&self, ctx: &Context); 
+pub use inspector::Inspector;
+pub use profiler::Profiler;
+pub use registry::{Panel, UIRegistry};
+pub use settings::Settings;
+
+pub trait UI {
+   fn ui(&mut self, ctx: &Context, state: &mut EngineState);
 }
@@ -0,0 +1,38 @@
+use egui::{Context, Grid, Window};
+
+use crate::engine::state::EngineState;
+
+use super::UI;
+
+// Frame timing and what the GPU culling pass kept.
+#[derive(Default)]
+pub struct Profiler {}
+
+impl UI for Profiler {
+    fn ui(&mut self, ctx: &Context, state: &mut EngineState) {
+        Window::new("Profiler").show(ctx, |ui| {
+            let time = state.frame_time;
+            let culling = state.culling;
+            let milliseconds = time.delta.as_secs_f64() * 1000.0;
+            let fps = if milliseconds > 0.0 { 1000.0 / milliseconds } else { 0.0 };
+            let culled_percent = if culling.tested > 0 {
+                100.0 * culling.culled() as f64 / culling.tested as f64
+            } else {
+                0.0
+            };
+            Grid::new("profiler").striped(true).show(ui, |ui| {
+                for (label, value) in [
+                    ("Frame", time.frame.to_string()),
+                    ("Frame time", format!("{milliseconds:.2} ms ({fps:.0} fps)")),
+                    ("Instances tested", culling.tested.to_string()),
+                    ("Visible", culling.visible.to_string()),
+                    ("Culled", format!("{} ({culled_percent:.1}%)", culling.culled())),
+                ] {
+                    ui.label(label);
+                    ui.monospace(value);
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,80 @@
+use egui::{menu, Context, TopBottomPanel};
+
+use crate::engine::state::EngineState;
+
+use super::UI;
+
+pub struct Panel {
+    pub name: String,
+    pub open: bool,
+    ui: Box<dyn UI>,
+}
+
+// Holds every panel drawn in the per-frame egui pass, together with a menu bar
+// that toggles them.
+#[derive(Default)]
+pub struct UIRegistry {
+    panels: Vec<Panel>,
+}
+
+impl UIRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: UI + 'static>(&mut self, name: &str, panel: T) {
+        self.panels.push(Panel {
+            name: name.to_string(),
+            open: true,
+            ui: Box::new(panel),
+        });
+    }
+
+    pub fn with_panel<T: UI + 'static>(mut self, name: &str, panel: T) -> Self {
+        self.register(name, panel);
+        self
+    }
+
+    pub fn append(&mut self, other: UIRegistry) {
+        self.panels.extend(other.panels);
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.panels.len();
+        self.panels.retain(|panel| panel.name != name);
+        self.panels.len() != len
+    }
+
+    pub fn panels(&self) -> &[Panel] {
+        &self.panels
+    }
+
+    pub fn set_open(&mut self, name: &str, open: bool) {
+        if let Some(panel) = self.panels.iter_mut().find(|panel| panel.name == name) {
+            panel.open = open;
+        }
+    }
+
+    pub fn is_open(&self, name: &str) -> bool {
+        self.panels
+            .iter()
+            .any(|panel| panel.name == name && panel.open)
+    }
+}
+
+impl UI for UIRegistry {
+    fn ui(&mut self, ctx: &Context, state: &mut EngineState) {
+        TopBottomPanel::top("Menu Bar").show(ctx, |ui| {
+            menu::bar(ui, |ui| {
+                ui.menu_button("Windows", |ui| {
+                    for panel in self.panels.iter_mut() {
+                        ui.checkbox(&mut panel.open, &panel.name);
+                    }
+                });
+            });
+        });
+        for panel in self.panels.iter_mut().filter(|panel| panel.open) {
+            panel.ui.ui(ctx, state);
+        }
+    }
+}
@@ -1,21 +1,72 @@
-use egui::panel::Side::Right;
+use egui::{panel::Side::Right, ComboBox, DragValue, Ui, Vec2};
+
+use crate::engine::{components::ComputePushConstants, state::EngineState};
 
 use super::UI;
 
-struct Settings {
+#[derive(Default)]
+pub struct Settings {
 
 }
 
+fn vec4_editor(ui: &mut Ui, label: &str, value: &mut [f32; 4]) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        for component in value.iter_mut() {
+            ui.add(DragValue::new(component).speed(0.01));
+        }
+    });
+}
 
 impl UI for Settings {
-    fn ui(&self, ctx: &egui::Context) {
+    fn ui(&mut self, ctx: &egui::Context, state: &mut EngineState) {
         egui::SidePanel::new(Right, "Side Panel")
             .show(&ctx, |ui|
-                {    ui.label("Hello World!");
-                     let hello_button = ui.button("Hellooo");
-                     if hello_button.clicked() {
-
-                     }
+                {
+                    ui.heading("Background");
+                    let selected_name = state
+                        .effects
+                        .get(state.current_effect)
+                        .map(|effect| effect.name.clone())
+                        .unwrap_or_default();
+                    ComboBox::from_label("Effect")
+                        .selected_text(selected_name)
+                        .show_ui(ui, |ui| {
+                            for (idx, effect) in state.effects.iter().enumerate() {
+                                ui.selectable_value(&mut state.current_effect, idx, &effect.name);
+                            }
+                        });
+                    let Some(effect) = state.effects.get_mut(state.current_effect) else {
+                        return;
+                    };
+                    let parameters = &mut effect.parameters;
+                    ui.horizontal(|ui| {
+                        ui.label("data1");
+                        ui.color_edit_button_rgba_unmultiplied(&mut parameters.data1);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("data2");
+                        ui.color_edit_button_rgba_unmultiplied(&mut parameters.data2);
+                    });
+                    vec4_editor(ui, "data3", &mut parameters.data3);
+                    vec4_editor(ui, "data4", &mut parameters.data4);
+                    ui.label(format!(
+                        "time: {:.2}s, frame {}",
+                        parameters.time, parameters.frame
+                    ));
+                    if ui.button("Reset").clicked() {
+                        effect.parameters = ComputePushConstants {
+                            time: effect.parameters.time,
+                            delta_time: effect.parameters.delta_time,
+                            frame: effect.parameters.frame,
+                            ..effect.default_parameters
+                        };
+                    }
+                    if let Some(texture) = state.textures.get("draw_image") {
+                        ui.collapsing("Preview", |ui| {
+                            ui.image((*texture, Vec2::new(256.0, 144.0)));
+                        });
+                    }
                 }
             );
     }
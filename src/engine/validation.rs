@@ -0,0 +1,299 @@
+use std::{
+    env,
+    ffi::c_void,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use ash::vk::{
+    DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT,
+    DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerCreateInfoEXT,
+};
+use log::{error, info, warn};
+
+pub const VALIDATION_SEVERITY_ENV: &str = "WURMPLE_VALIDATION";
+pub const VALIDATION_ON_ERROR_ENV: &str = "WURMPLE_VALIDATION_ON_ERROR";
+pub const VALIDATION_LAYER_ENV: &str = "WURMPLE_VALIDATION_LAYER";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorAction {
+    Log,
+    // Panics from `ValidationSink::check` at the end of the frame, unwinding out of
+    // the driver callback itself would abort.
+    Panic,
+    // Traps into an attached debugger from inside the callback.
+    Break,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ValidationConfig {
+    pub severities: DebugUtilsMessageSeverityFlagsEXT,
+    pub on_error: ErrorAction,
+    // Enable VK_LAYER_KHRONOS_validation on the instance instead of relying on it being
+    // switched on from outside (vkconfig, VK_INSTANCE_LAYERS).
+    pub enable_layer: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            severities: DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            on_error: ErrorAction::Log,
+            enable_layer: false,
+        }
+    }
+}
+
+impl ValidationConfig {
+    // WURMPLE_VALIDATION picks the lowest severity that is reported (verbose, info,
+    // warning, error), WURMPLE_VALIDATION_ON_ERROR one of log, panic or break and
+    // WURMPLE_VALIDATION_LAYER=1 enables the layer itself.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(severity) = env::var(VALIDATION_SEVERITY_ENV) {
+            let lowest = match severity.to_lowercase().as_str() {
+                "verbose" => DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+                "info" => DebugUtilsMessageSeverityFlagsEXT::INFO,
+                "error" => DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                _ => DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            };
+            config.severities = [
+                DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+                DebugUtilsMessageSeverityFlagsEXT::INFO,
+                DebugUtilsMessageSeverityFlagsEXT::WARNING,
+                DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            ]
+            .into_iter()
+            .filter(|severity| severity.as_raw() >= lowest.as_raw())
+            .fold(DebugUtilsMessageSeverityFlagsEXT::empty(), |acc, severity| {
+                acc | severity
+            });
+        }
+        if let Ok(action) = env::var(VALIDATION_ON_ERROR_ENV) {
+            config.on_error = match action.to_lowercase().as_str() {
+                "panic" => ErrorAction::Panic,
+                "break" => ErrorAction::Break,
+                _ => ErrorAction::Log,
+            };
+        }
+        if let Ok(layer) = env::var(VALIDATION_LAYER_ENV) {
+            config.enable_layer = matches!(layer.to_lowercase().as_str(), "1" | "true" | "on");
+        }
+        config
+    }
+
+    pub fn with_severities(mut self, severities: DebugUtilsMessageSeverityFlagsEXT) -> Self {
+        self.severities = severities;
+        self
+    }
+
+    pub fn with_on_error(mut self, on_error: ErrorAction) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    pub fn with_enable_layer(mut self, enable_layer: bool) -> Self {
+        self.enable_layer = enable_layer;
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ValidationMessage {
+    pub severity: DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: DebugUtilsMessageTypeFlagsEXT,
+    pub message_id_name: String,
+    pub message_id_number: i32,
+    pub message: String,
+    pub objects: Vec<String>,
+}
+
+impl ValidationMessage {
+    pub fn is_error(&self) -> bool {
+        self.severity.contains(DebugUtilsMessageSeverityFlagsEXT::ERROR)
+    }
+}
+
+impl fmt::Display for ValidationMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} [{} ({})] : {}",
+            self.message_type, self.message_id_name, self.message_id_number, self.message
+        )?;
+        if !self.objects.is_empty() {
+            write!(f, " objects: {}", self.objects.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// Collects every message the validation layer reports through the debug messenger.
+// The messenger keeps a raw pointer to the sink, so it has to outlive the instance.
+pub struct ValidationSink {
+    config: ValidationConfig,
+    messages: Mutex<Vec<ValidationMessage>>,
+    layer_enabled: AtomicBool,
+}
+
+impl ValidationSink {
+    pub fn new(config: ValidationConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            messages: Mutex::new(Vec::new()),
+            layer_enabled: AtomicBool::new(false),
+        })
+    }
+
+    pub fn config(&self) -> ValidationConfig {
+        self.config
+    }
+
+    // Whether the instance was created with the validation layer enabled through
+    // `ValidationConfig::enable_layer`. Without it an empty sink proves nothing.
+    pub fn layer_enabled(&self) -> bool {
+        self.layer_enabled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_layer_enabled(&self, enabled: bool) {
+        self.layer_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn messenger_create_info(self: &Arc<Self>) -> DebugUtilsMessengerCreateInfoEXT<'static> {
+        DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(self.config.severities)
+            .message_type(
+                DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    | DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            )
+            .pfn_user_callback(Some(debug_callback))
+            .user_data(Arc::as_ptr(self) as *mut c_void)
+    }
+
+    fn push(&self, message: ValidationMessage) {
+        match message.severity {
+            DebugUtilsMessageSeverityFlagsEXT::ERROR => error!("{message}"),
+            DebugUtilsMessageSeverityFlagsEXT::WARNING => warn!("{message}"),
+            _ => info!("{message}"),
+        }
+        let is_error = message.is_error();
+        self.messages.lock().unwrap().push(message);
+        if is_error && self.config.on_error == ErrorAction::Break {
+            breakpoint();
+        }
+    }
+
+    pub fn messages(&self) -> Vec<ValidationMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    pub fn take(&self) -> Vec<ValidationMessage> {
+        std::mem::take(&mut *self.messages.lock().unwrap())
+    }
+
+    pub fn clear(&self) {
+        self.messages.lock().unwrap().clear();
+    }
+
+    pub fn errors(&self) -> Vec<ValidationMessage> {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|message| message.is_error())
+            .cloned()
+            .collect()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|message| message.is_error())
+            .count()
+    }
+
+    // Called once per frame, applies the configured error action.
+    pub fn check(&self) {
+        if self.config.on_error == ErrorAction::Panic {
+            self.assert_no_errors();
+        }
+    }
+
+    pub fn assert_no_errors(&self) {
+        let errors = self.errors();
+        assert!(
+            errors.is_empty(),
+            "{} validation errors:\n{}",
+            errors.len(),
+            errors
+                .iter()
+                .map(|message| message.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+}
+
+fn breakpoint() {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        std::arch::asm!("int3")
+    };
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        std::arch::asm!("brk #0xf000")
+    };
+}
+
+unsafe extern "system" fn debug_callback(
+    message_severity: DebugUtilsMessageSeverityFlagsEXT,
+    message_type: DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const DebugUtilsMessengerCallbackDataEXT<'_>,
+    user_data: *mut c_void,
+) -> u32 {
+    unsafe {
+        let callback_data = *callback_data;
+        let objects = if callback_data.p_objects.is_null() {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts(callback_data.p_objects, callback_data.object_count as usize)
+        };
+        let message = ValidationMessage {
+            severity: message_severity,
+            message_type,
+            message_id_name: callback_data
+                .message_id_name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            message_id_number: callback_data.message_id_number,
+            message: callback_data
+                .message_as_c_str()
+                .map(|message| message.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            objects: objects
+                .iter()
+                .map(|object| match object.object_name_as_c_str() {
+                    Some(name) => format!(
+                        "{} ({:?} {:#x})",
+                        name.to_string_lossy(),
+                        object.object_type,
+                        object.object_handle
+                    ),
+                    None => format!("{:?} {:#x}", object.object_type, object.object_handle),
+                })
+                .collect(),
+        };
+        match (user_data as *const ValidationSink).as_ref() {
+            Some(sink) => sink.push(message),
+            None => warn!("{message}"),
+        }
+    }
+    0
+}
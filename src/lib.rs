@@ -1,2 +1,5 @@
 pub mod app;
+pub mod application;
 pub mod engine;
+
+pub use application::{run, AppConfig, Application, RedrawMode, RenderContext};
@@ -0,0 +1,11 @@
+// Leaks are counted process wide, so this stays the only test in its binary. Needs a
+// Vulkan device and a display:
+//
+// cargo test --features testing --test leaks -- --ignored
+use wurmple::engine::testing::assert_frames_render_without_leaks;
+
+#[test]
+#[ignore = "needs a Vulkan device and a display"]
+fn frames_render_without_leaks() {
+    assert_frames_render_without_leaks(3);
+}
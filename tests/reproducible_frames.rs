@@ -0,0 +1,10 @@
+// Needs a Vulkan device and a display:
+//
+// cargo test --features testing --test reproducible_frames -- --ignored
+use wurmple::engine::testing::assert_frames_render_reproducibly;
+
+#[test]
+#[ignore = "needs a Vulkan device and a display"]
+fn frame_n_renders_the_same_every_run() {
+    assert_frames_render_reproducibly(10);
+}
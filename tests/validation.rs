@@ -0,0 +1,11 @@
+// Needs a Vulkan device with the validation layers installed and a display, so it only
+// runs when asked for:
+//
+// cargo test --features testing --test validation -- --ignored
+use wurmple::engine::testing::assert_frames_render_without_validation_errors;
+
+#[test]
+#[ignore = "needs a Vulkan device, the validation layers and a display"]
+fn frames_render_without_validation_errors() {
+    assert_frames_render_without_validation_errors(3);
+}